@@ -1,23 +1,293 @@
+use crate::compression::Compression;
+use crate::nbt::{tag_name, NbtFlavor, Tag};
 use core::array::TryFromSliceError;
-use mutf8::MString;
+use mutf8::{mutf8_to_utf8, utf8_to_mutf8, MString};
 use thiserror::Error;
 
 pub type BinResult<T> = std::result::Result<T, BinError>;
 
+fn at_byte(pos: &Option<usize>) -> String {
+    pos.map(|pos| format!(" at byte {}", pos))
+        .unwrap_or_default()
+}
+
 #[derive(Error, Debug)]
 pub enum BinError {
     #[error("Unexpected end of byte stream")]
     UnexpectedEndOfByteStream,
+    ///a whole document or file given as zero bytes, like a `.dat` file that was never written
+    #[error("The input is empty")]
+    EmptyInput,
     #[error(transparent)]
     ParsingPrimitive(#[from] TryFromSliceError),
-    #[error("Parsing failed")]
+    #[error("Parsing failed: {0}")]
     Parsing(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    ///a tag id outside of `0x00..=0x0C`, `pos` is the offset its payload would start at
+    #[error("Invalid Tag ID: {tag_id} at byte {pos}")]
+    InvalidTagId { tag_id: u8, pos: usize },
+    ///a byte read with [`FileReader::expect_u8`] that isn't the expected one
+    #[error("Expected byte 0x{expected:02X}, found 0x{found:02X} at byte {pos}")]
+    UnexpectedByte { expected: u8, found: u8, pos: usize },
+    ///a list or array length needing more bytes than the reader has left, checked before
+    ///reading elements of a fixed size, `pos` is where the elements start
+    #[error("Length {len} needs at least {needed} bytes at byte {pos}, only {remaining} are left")]
+    LengthExceedsInput {
+        len: i32,
+        needed: usize,
+        remaining: usize,
+        pos: usize,
+    },
+    ///a tag of one type added to a list of another
+    #[error("Can't put a {} into a list of {}", tag_name(*.element_id), tag_name(*.list_id))]
+    HeterogeneousList { list_id: u8, element_id: u8 },
+    ///a list of `TAG_End` with a nonzero length
+    #[error("Lists of type \"Tag End\" can't have a length greater than 0, found {0}")]
+    NonEmptyEndList(i32),
+    ///an empty list declaring an element type other than `TAG_End`, only an error in strict mode
+    #[error("Empty lists must be of type \"Tag End\", found an empty list of {}", tag_name(*.0))]
+    TypedEmptyList(u8),
+    ///lists and compounds nested deeper than [`Limits::max_depth`], `pos` is where the one
+    ///over the limit starts
+    #[error("NBT nested deeper than {max_depth} lists and compounds at byte {pos}")]
+    DepthExceeded { max_depth: usize, pos: usize },
+    ///a string that isn't valid ``MUTF-8``, only an error in strict mode
+    #[error("Invalid MUTF-8 string: {0:02X?}")]
+    InvalidMutf8(Vec<u8>),
+    ///a varint longer than 10 bytes or too large for the integer `ty` it's read as
+    #[error("VarInt doesn't fit a {ty}")]
+    VarIntOverflow { ty: &'static str },
+    ///a Bedrock `level.dat` whose header declares another NBT length than the bytes following it
+    #[error("Bedrock level.dat declares {declared} bytes of NBT but {found} follow its header")]
+    LevelLengthMismatch { declared: u32, found: usize },
+    ///a region chunk's compression byte, without the external flag, that isn't 1, 2 or 3
+    #[error("Unknown chunk compression scheme: {0}")]
+    UnknownCompressionScheme(u8),
+    ///a number coerced to a numeric type it doesn't fit, like 128 to a byte or 1.5 to an int,
+    ///`value` being the tag as it was
+    #[error("{value:?} doesn't fit a {}", tag_name(*.target_id))]
+    NumberOutOfRange { value: Tag, target_id: u8 },
+    ///a tag or tag id used as a number that isn't one of the numeric tags
+    #[error("{} isn't a numeric tag", tag_name(*.0))]
+    NotNumeric(u8),
+    ///region data shorter than the two header sectors
+    #[error("Region data is {len} bytes, the header alone needs {needed}")]
+    RegionHeaderTooShort { len: usize, needed: usize },
+    ///chunk coordinates within a region that aren't both in `0..32`
+    #[error("Chunk ({x}, {z}) is outside of the region (0..32)")]
+    ChunkOutOfRegion { x: u8, z: u8 },
+    ///a chunk needing more sectors than a region's location table can give it
+    #[error("Chunk ({x}, {z}) needs {sectors} sectors, at most 255 fit in a region file")]
+    ChunkTooLarge { x: u8, z: u8, sectors: usize },
+    ///an external chunk of a region that wasn't read from a file, so there's no `c.X.Z.mcc` to look for
+    #[error("Chunk ({x}, {z}) is stored externally but the region has no file path")]
+    NoRegionPath { x: u8, z: u8 },
+    ///a region file whose name isn't `r.X.Z.mca`
+    #[error("Can't get region coordinates from {}", .0.display())]
+    InvalidRegionFileName(std::path::PathBuf),
+    ///the `c.X.Z.mcc` file of an external chunk that doesn't exist
+    #[error("Chunk is stored externally but {} is missing", .path.display())]
+    MissingExternalChunk { path: std::path::PathBuf },
+    ///a document starting with neither a compression's magic number nor a tag id
+    #[error("Unrecognized compression, the document starts with {0:#04x}")]
+    UnrecognizedCompression(u8),
+    ///a list widened to a type its elements don't all fit, see [`crate::nbt::List::widen_to`]
+    #[error("Can't widen a list of {} to a list of {}", tag_name(*.from), tag_name(*.to))]
+    IncompatibleListWiden { from: u8, to: u8 },
+    ///a chunk section without a block state palette
+    #[error("Chunk section has no block state palette")]
+    MissingBlockPalette,
+    ///a chunk section with a palette of several block states but no data indexing it
+    #[error("Chunk section has no block state data")]
+    MissingBlockStates,
+    ///block state data of another length than its palette size and data version call for
+    #[error("Block state data is {len} longs, {bits} bits per entry need {expected}")]
+    BlockStatesLength {
+        len: usize,
+        bits: usize,
+        expected: usize,
+    },
+    ///a block state indexing past the end of the palette
+    #[error("Block state {block} points at palette entry {entry} of {palette_len}")]
+    BlockStateOutOfPalette {
+        block: usize,
+        entry: u64,
+        palette_len: usize,
+    },
+    ///a patch operation other than set and remove, see [`crate::nbt::apply_binary`]
+    #[error("Invalid patch operation: {0}")]
+    InvalidPatchOp(u8),
+    ///a patch removing the root tag rather than something inside it
+    #[error("Can't remove the root tag")]
+    PatchRemovesRoot,
+    ///a patch path running through a missing key or a tag that isn't a compound
+    #[error("Patch path doesn't exist: {}", .0.join("."))]
+    MissingPatchPath(Vec<String>),
+    ///a compound key seen twice while reading in strict mode
+    #[error("Duplicate compound key: {0}")]
+    DuplicateKey(String),
+    #[error("Missing required key {0:?}")]
+    MissingKey(String),
+    ///a tag of another type than the one asked for
+    #[error("Expected a {}, found a {}", tag_name(*.expected), tag_name(*.found))]
+    UnexpectedTagType { expected: u8, found: u8 },
+    ///a tag the NBT flavor being read or written doesn't have, like long arrays in Bedrock NBT,
+    ///`pos` is the offset its payload would start at when reading and `None` when writing
+    #[error("{} isn't supported by {flavor:?} NBT{}", tag_name(*.tag_id), at_byte(.pos))]
+    UnsupportedTag {
+        tag_id: u8,
+        flavor: NbtFlavor,
+        pos: Option<usize>,
+    },
+    ///another error along with where it happened, like the key of a field or the index of a document
+    #[error("{context}: {source}")]
+    Context {
+        context: String,
+        source: Box<BinError>,
+    },
+}
+
+impl BinError {
+    ///wraps the error with a note on where it happened
+    pub fn context(self, context: impl Into<String>) -> Self {
+        BinError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    ///the innermost error, skipping the [`BinError::Context`] layers
+    pub fn root_cause(&self) -> &BinError {
+        match self {
+            BinError::Context { source, .. } => source.root_cause(),
+            e => e,
+        }
+    }
+}
+
+///settings readers carry for the parsers built on top of them
+#[derive(Clone, Copy, Debug)]
+pub struct ReadOptions {
+    ///reject input the lenient defaults would accept, like duplicate compound keys
+    ///(otherwise the last value of a key is kept), invalid ``MUTF-8`` (otherwise replaced)
+    ///or empty lists with an element type other than `TAG_End` (otherwise read as `List::Empty`)
+    pub strict: bool,
+    ///entries reserved up front for every compound read, compounds have no length prefix so
+    ///this only saves the first few rehashes, ignored with the `sorted_compounds` feature
+    pub compound_capacity: usize,
+    ///payload size of tag ids this crate doesn't know (above `0x0C`), like ids added by mods
+    ///
+    ///compound entries of those ids are skipped and lists of them read as empty, without it
+    ///or when it returns `None` they're an error giving the id and the byte offset of the payload
+    pub unknown_tag_size: Option<fn(u8) -> Option<usize>>,
+    ///how `i32`s, `i64`s and string lengths are encoded, see [`IntEncoding`]
+    pub ints: IntEncoding,
+    ///bounds on the input beyond its format, none by default, see [`Limits::vanilla`]
+    pub limits: Limits,
+    ///how much capacity arrays and lists of numbers or arrays reserve up front for their declared length
+    pub reserve: ReservePolicy,
+    ///the NBT flavor being read, tag ids it doesn't have are a [`BinError::UnsupportedTag`]
+    ///as soon as they're read, before their payload, set by [`Tag::read_with`]
+    pub flavor: Option<NbtFlavor>,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            compound_capacity: 8,
+            unknown_tag_size: None,
+            ints: IntEncoding::Fixed,
+            limits: Limits::default(),
+            reserve: ReservePolicy::Exact,
+            flavor: None,
+        }
+    }
+}
+
+///how much of a declared length arrays and lists of numbers or arrays reserve before reading them
+///
+///declared lengths are already checked against the bytes left in the reader
+///(see [`BinError::LengthExceedsInput`]), so even `Exact` never reserves more than the input
+///could fill, `Capped` bounds memory further for large untrusted input at the cost of regrowing
+///vectors past the cap, lists of strings, lists and compounds always grow as they're read
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ReservePolicy {
+    ///reserve the declared length, the fastest for trusted input like local files
+    #[default]
+    Exact,
+    ///reserve at most this many elements, like for input from the network
+    Capped(usize),
+    ///reserve nothing, vectors grow as elements are read
+    None,
+}
+
+///bounds readers enforce on top of the format itself, the default has none
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Limits {
+    ///most lists and compounds a tag can be nested in, the root compound counting as one,
+    ///deeper input is a [`BinError::DepthExceeded`]
+    pub max_depth: Option<usize>,
+}
+
+impl Limits {
+    ///the nesting vanilla Minecraft's `NbtAccounter` accepts (1.20.2 and later), so files
+    ///are rejected exactly when vanilla would reject them for their shape
+    ///
+    ///- `max_depth`: 512, `NbtAccounter.MAX_STACK_DEPTH`, which every list and compound read
+    ///  counts against, a root compound with 511 compounds nested inside still reads, one more doesn't
+    ///
+    ///vanilla's other bound, the 2 MiB quota of NBT read from the network, is counted against
+    ///its own estimate of the JVM heap the tags take and has no byte for byte equivalent here,
+    ///strings are already bounded to 65535 ``MUTF-8`` bytes by their `u16` length
+    ///
+    ///reading recurses once per level, the full 512 levels can take a few MiB of stack in debug builds
+    pub fn vanilla() -> Self {
+        Self {
+            max_depth: Some(VANILLA_MAX_DEPTH),
+        }
+    }
+}
+
+///nesting limit of vanilla's `NbtAccounter`, see [`Limits::vanilla`]
+pub const VANILLA_MAX_DEPTH: usize = 512;
+
+///settings writers carry for the serializers built on top of them
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WriteOptions {
+    ///how `i32`s, `i64`s and string lengths are encoded, see [`IntEncoding`]
+    pub ints: IntEncoding,
+}
+
+///byte order of a reader or writer
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Endianness {
+    Big,
+    Little,
+}
+
+///how readers and writers encode `i32`s and `i64`s, NBT's ints, longs and list and array lengths,
+///along with the `u16` length of strings
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum IntEncoding {
+    ///fixed width in the reader's or writer's endianness
+    #[default]
+    Fixed,
+    ///zigzag varints, string lengths as unsigned varints, like Bedrock network NBT
+    VarInt,
 }
 
 pub trait Writer {
     fn write(&self, fw: &mut impl FileWriter);
 }
 
+impl<T: Writer + ?Sized> Writer for &T {
+    fn write(&self, fw: &mut impl FileWriter) {
+        (**self).write(fw)
+    }
+}
+
 pub trait Io: Writer {
     fn read(fr: &mut impl FileReader) -> BinResult<Self>
     where
@@ -36,7 +306,7 @@ pub trait PrimitiveIo: Io {
 }
 
 macro_rules! io_primitive {
-    ($type:tt, $size:literal) => {
+    (@primitive $type:tt, $size:literal) => {
         impl PrimitiveIo for $type {
             fn primitive_read_be(fr: &mut impl FileReader) -> BinResult<Self> {
                 let bytes = fr.get_slice($size)?;
@@ -57,13 +327,16 @@ macro_rules! io_primitive {
             }
 
             fn primitive_write_be(&self, fw: &mut impl FileWriter) {
-                fw.append(&mut Self::to_be_bytes(*self).to_vec())
+                fw.extend(&Self::to_be_bytes(*self))
             }
 
             fn primitive_write_le(&self, fw: &mut impl FileWriter) {
-                fw.append(&mut Self::to_le_bytes(*self).to_vec())
+                fw.extend(&Self::to_le_bytes(*self))
             }
         }
+    };
+    ($type:tt, $size:literal) => {
+        io_primitive!(@primitive $type, $size);
 
         impl Io for $type {
             fn read(fr: &mut impl FileReader) -> BinResult<Self> {
@@ -77,6 +350,132 @@ macro_rules! io_primitive {
             }
         }
     };
+    ($type:tt, $size:literal, $unsigned:ty) => {
+        io_primitive!(@primitive $type, $size);
+
+        impl Io for $type {
+            ///a zigzag varint if the reader's [`ReadOptions::ints`] say so
+            fn read(fr: &mut impl FileReader) -> BinResult<Self> {
+                match fr.options().ints {
+                    IntEncoding::Fixed => fr.primitive_read(),
+                    IntEncoding::VarInt => {
+                        let v = <$unsigned>::try_from(fr.read_varint()?).map_err(|_| {
+                            BinError::VarIntOverflow {
+                                ty: stringify!($type),
+                            }
+                        })?;
+                        Ok((v >> 1) as $type ^ -((v & 1) as $type))
+                    }
+                }
+            }
+        }
+
+        impl Writer for $type {
+            ///a zigzag varint if the writer's [`WriteOptions::ints`] say so
+            fn write(&self, fw: &mut impl FileWriter) {
+                match fw.options().ints {
+                    IntEncoding::Fixed => fw.primitive_write(self),
+                    IntEncoding::VarInt => {
+                        let v = (*self << 1) ^ (*self >> ($size * 8 - 1));
+                        fw.write_varint(v as $unsigned as u64)
+                    }
+                }
+            }
+        }
+    };
+}
+
+///the numbers arrays and lists are made of, read and written in bulk with the `simd` feature
+pub(crate) trait BulkIo: PrimitiveIo + Copy {
+    ///whether the number is a zigzag varint with [`IntEncoding::VarInt`], bulk copies only
+    ///apply to fixed size encodings
+    #[cfg(feature = "simd")]
+    const VARINT: bool;
+
+    ///reads `len` numbers with a single bounds check, converting them in a loop the compiler
+    ///can vectorize
+    #[cfg(feature = "simd")]
+    fn read_bulk(fr: &mut impl FileReader, len: usize, out: &mut Vec<Self>) -> BinResult<()>;
+
+    ///writes the numbers through a stack buffer, converting them in a loop the compiler
+    ///can vectorize
+    #[cfg(feature = "simd")]
+    fn write_bulk(values: &[Self], fw: &mut impl FileWriter);
+}
+
+#[cfg(feature = "simd")]
+const BULK_BUFFER_SIZE: usize = 4096;
+
+macro_rules! bulk_io {
+    ($type:ty, $size:literal, $varint:literal) => {
+        impl BulkIo for $type {
+            #[cfg(feature = "simd")]
+            const VARINT: bool = $varint;
+
+            #[cfg(feature = "simd")]
+            fn read_bulk(
+                fr: &mut impl FileReader,
+                len: usize,
+                out: &mut Vec<Self>,
+            ) -> BinResult<()> {
+                let big = fr.endianness() == Endianness::Big;
+                let len = len
+                    .checked_mul($size)
+                    .ok_or(BinError::UnexpectedEndOfByteStream)?;
+                let chunks = fr.get_slice(len)?.chunks_exact($size);
+                if big {
+                    out.extend(chunks.map(|c| <$type>::from_be_bytes(c.try_into().unwrap())));
+                } else {
+                    out.extend(chunks.map(|c| <$type>::from_le_bytes(c.try_into().unwrap())));
+                }
+                Ok(())
+            }
+
+            #[cfg(feature = "simd")]
+            fn write_bulk(values: &[Self], fw: &mut impl FileWriter) {
+                let big = fw.endianness() == Endianness::Big;
+                let mut buf = [0x00; BULK_BUFFER_SIZE];
+                for values in values.chunks(BULK_BUFFER_SIZE / $size) {
+                    let bytes = &mut buf[..values.len() * $size];
+                    let chunks = bytes.chunks_exact_mut($size).zip(values);
+                    if big {
+                        chunks.for_each(|(c, v)| c.copy_from_slice(&v.to_be_bytes()));
+                    } else {
+                        chunks.for_each(|(c, v)| c.copy_from_slice(&v.to_le_bytes()));
+                    }
+                    fw.extend(bytes);
+                }
+            }
+        }
+    };
+}
+
+///reads `len` numbers into `out`, in bulk with the `simd` feature unless they're varints
+pub(crate) fn read_numbers<T: BulkIo>(
+    fr: &mut impl FileReader,
+    len: usize,
+    out: &mut Vec<T>,
+) -> BinResult<()> {
+    #[cfg(feature = "simd")]
+    if !(T::VARINT && fr.options().ints == IntEncoding::VarInt) {
+        return T::read_bulk(fr, len, out);
+    }
+    for _ in 0..len {
+        out.push(fr.read()?);
+    }
+    Ok(())
+}
+
+///writes the numbers without a length prefix, in bulk with the `simd` feature unless
+///they're varints, the bytes are the same either way
+pub(crate) fn write_numbers<T: BulkIo>(values: &[T], fw: &mut impl FileWriter) {
+    #[cfg(feature = "simd")]
+    if !(T::VARINT && fw.options().ints == IntEncoding::VarInt) {
+        return T::write_bulk(values, fw);
+    }
+    for v in values {
+        v.write(fw);
+    }
 }
 
 io_primitive!(u8, 1);
@@ -84,28 +483,58 @@ io_primitive!(i8, 1);
 io_primitive!(u16, 2);
 io_primitive!(i16, 2);
 io_primitive!(u32, 4);
-io_primitive!(i32, 4);
+io_primitive!(i32, 4, u32);
 io_primitive!(u64, 8);
-io_primitive!(i64, 8);
+io_primitive!(i64, 8, u64);
 io_primitive!(f32, 4);
 io_primitive!(f64, 8);
 
+bulk_io!(i8, 1, false);
+bulk_io!(i16, 2, false);
+bulk_io!(i32, 4, true);
+bulk_io!(i64, 8, true);
+bulk_io!(f32, 4, false);
+bulk_io!(f64, 8, false);
+
 impl Io for String {
+    ///invalid ``MUTF-8`` is replaced with U+FFFD, or is an error in strict mode
     fn read(fr: &mut impl FileReader) -> BinResult<Self> {
-        let len = fr.read::<u16>()? as usize;
-        Ok(MString::from_mutf8(fr.get_slice(len)?).to_string())
+        let len = read_string_len(fr)?;
+        let strict = fr.options().strict;
+        let bytes = fr.get_slice(len)?;
+        match mutf8_to_utf8(bytes).map(|utf8| String::from_utf8(utf8.into_owned())) {
+            Ok(Ok(s)) => Ok(s),
+            _ if strict => Err(BinError::InvalidMutf8(bytes.to_vec())),
+            _ => Ok(MString::from_mutf8(bytes).to_string()),
+        }
     }
 }
 
+///borrows `s` unless it has characters ``MUTF-8`` encodes differently to UTF-8
+pub(crate) fn encode_mutf8(s: &str) -> std::borrow::Cow<'_, [u8]> {
+    utf8_to_mutf8(s.as_bytes()).expect("a str is valid UTF-8")
+}
+
 impl Writer for String {
+    ///the length prefix counts the encoded bytes, nul and supplementary characters grow
     fn write(&self, fw: &mut impl FileWriter) {
-        fw.write(&(self.len() as u16));
-        fw.append(
-            &mut MString::from_utf8(String::as_bytes(self))
-                .unwrap()
-                .as_mutf8_bytes()
-                .to_vec(),
-        );
+        let bytes = encode_mutf8(self);
+        write_string_len(bytes.len(), fw);
+        fw.extend(&bytes);
+    }
+}
+
+fn read_string_len(fr: &mut impl FileReader) -> BinResult<usize> {
+    match fr.options().ints {
+        IntEncoding::Fixed => Ok(fr.read::<u16>()? as usize),
+        IntEncoding::VarInt => Ok(fr.read_varint()? as usize),
+    }
+}
+
+fn write_string_len(len: usize, fw: &mut impl FileWriter) {
+    match fw.options().ints {
+        IntEncoding::Fixed => fw.write(&(len as u16)),
+        IntEncoding::VarInt => fw.write_varint(len as u64),
     }
 }
 
@@ -132,8 +561,71 @@ pub trait FileReader: PrimitiveFileReader {
     where
         Self: Sized;
     fn get_slice(&mut self, len: usize) -> BinResult<&[u8]>;
+    ///reads a byte, erroring if it isn't `expected`
+    fn expect_u8(&mut self, expected: u8) -> BinResult<()> {
+        let pos = self.position();
+        let found = self.get_slice(1)?[0];
+        if found != expected {
+            return Err(BinError::UnexpectedByte {
+                expected,
+                found,
+                pos,
+            });
+        }
+        Ok(())
+    }
+    ///reads a tag id, erroring if it isn't `tag_id`, like `expect_tag_id(0x00)` at the end of a compound
+    fn expect_tag_id(&mut self, tag_id: u8) -> BinResult<()> {
+        let pos = self.position();
+        let found = self.get_slice(1)?[0];
+        if found != tag_id {
+            return Err(BinError::UnexpectedTagType {
+                expected: tag_id,
+                found,
+            }
+            .context(format!("Tag id at byte {}", pos)));
+        }
+        Ok(())
+    }
+    ///reads a 3 byte ``big endian`` unsigned integer, like region file sector offsets
+    fn read_u24_be(&mut self) -> BinResult<u32> {
+        let b = self.get_slice(3)?;
+        Ok(u32::from_be_bytes([0x00, b[0], b[1], b[2]]))
+    }
+    ///reads a length prefixed string without decoding it, returning its ``MUTF-8`` bytes
+    fn read_raw_string_bytes(&mut self) -> BinResult<&[u8]>
+    where
+        Self: Sized,
+    {
+        let len = read_string_len(self)?;
+        self.get_slice(len)
+    }
+    ///reads an unsigned ``LEB128`` varint of at most 10 bytes, bits past the 64th are an error
+    fn read_varint(&mut self) -> BinResult<u64> {
+        let mut v = 0;
+        for i in 0..10 {
+            let b = self.get_slice(1)?[0];
+            //the 10th byte only has room for the 64th bit
+            if i == 9 && b > 0x01 {
+                return Err(BinError::VarIntOverflow { ty: "u64" });
+            }
+            v |= ((b & 0x7F) as u64) << (i * 7);
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(BinError::VarIntOverflow { ty: "u64" })
+    }
+    ///skips ahead to the next multiple of `alignment`
+    fn align_to(&mut self, alignment: usize) -> BinResult<()>;
     fn rest(&self) -> Vec<u8>;
     fn at_end(&self) -> bool;
+    ///offset of the next byte to be read
+    fn position(&self) -> usize;
+    ///the whole underlying byte buffer, including what's already been read
+    fn buffer(&self) -> &[u8];
+    fn options(&self) -> &ReadOptions;
+    fn endianness(&self) -> Endianness;
 }
 
 pub trait PrimitiveFileReader {
@@ -157,15 +649,44 @@ pub trait PrimitiveFileReader {
 }
 
 macro_rules! file_reader {
-    ($reader:ident, $endian:ident, $reader_inverse:ident, $endian_inverse:ident, $endian_primitive:ident) => {
+    ($reader:ident, $endianness:ident, $endian:ident, $reader_inverse:ident, $endian_inverse:ident, $endian_primitive:ident) => {
         pub struct $reader<'a> {
-            bytes: &'a Vec<u8>,
+            bytes: &'a [u8],
             pos: usize,
+            options: ReadOptions,
         }
 
         impl<'a> $reader<'a> {
-            pub fn new(bytes: &'a Vec<u8>, pos: usize) -> Self {
-                Self { bytes, pos }
+            ///reads `bytes` in place from `pos` on, any buffer dereferencing to `[u8]` works
+            ///without a copy
+            pub fn new(bytes: &'a [u8], pos: usize) -> Self {
+                Self {
+                    bytes,
+                    pos,
+                    options: ReadOptions::default(),
+                }
+            }
+
+            ///reads a `bytes::Bytes` in place from its start, like a packet straight out of a
+            ///network receive buffer
+            #[cfg(feature = "bytes")]
+            pub fn from_bytes(bytes: &'a bytes::Bytes) -> Self {
+                Self::new(bytes, 0)
+            }
+
+            pub fn with_options(mut self, options: ReadOptions) -> Self {
+                self.options = options;
+                self
+            }
+
+            ///a reader over the same buffer and with the same options, starting at `pos`,
+            ///like a chunk's sector inside a region, this reader stays where it is
+            pub fn split_at(&self, pos: usize) -> Self {
+                Self {
+                    bytes: self.bytes,
+                    pos,
+                    options: self.options,
+                }
             }
         }
 
@@ -181,18 +702,32 @@ macro_rules! file_reader {
             }
 
             fn $endian_inverse<T: Io>(&mut self) -> BinResult<T> {
-                let mut inverse = $reader_inverse::new(self.bytes, self.pos);
+                let mut inverse =
+                    $reader_inverse::new(self.bytes, self.pos).with_options(self.options);
                 let r = T::read(&mut inverse);
                 self.pos = inverse.pos;
                 r
             }
 
             fn get_slice(&mut self, len: usize) -> Result<&[u8], BinError> {
-                self.pos += len;
-                if self.pos > self.bytes.len() {
+                let end = match self.pos.checked_add(len) {
+                    Some(end) if end <= self.bytes.len() => end,
+                    _ => return Err(BinError::UnexpectedEndOfByteStream),
+                };
+                let start = std::mem::replace(&mut self.pos, end);
+                Ok(&self.bytes[start..end])
+            }
+
+            fn align_to(&mut self, alignment: usize) -> BinResult<()> {
+                if alignment == 0 {
+                    return Ok(());
+                }
+                let pos = self.pos.div_ceil(alignment) * alignment;
+                if pos > self.bytes.len() {
                     return Err(BinError::UnexpectedEndOfByteStream);
                 }
-                Ok(&self.bytes[self.pos - len..self.pos])
+                self.pos = pos;
+                Ok(())
             }
 
             fn rest(&self) -> Vec<u8> {
@@ -200,7 +735,23 @@ macro_rules! file_reader {
             }
 
             fn at_end(&self) -> bool {
-                self.pos == self.bytes.len()
+                self.pos >= self.bytes.len()
+            }
+
+            fn position(&self) -> usize {
+                self.pos
+            }
+
+            fn buffer(&self) -> &[u8] {
+                self.bytes
+            }
+
+            fn options(&self) -> &ReadOptions {
+                &self.options
+            }
+
+            fn endianness(&self) -> Endianness {
+                Endianness::$endianness
             }
         }
     };
@@ -208,6 +759,7 @@ macro_rules! file_reader {
 
 file_reader!(
     FileReaderBE,
+    Big,
     read_be,
     FileReaderLE,
     read_le,
@@ -215,6 +767,7 @@ file_reader!(
 );
 file_reader!(
     FileReaderLE,
+    Little,
     read_le,
     FileReaderBE,
     read_be,
@@ -229,13 +782,83 @@ pub trait FileWriter: PrimitiveFileWriter {
         v.write(self);
     }
 
+    ///writes every item in turn, without any length prefix
+    fn write_all<T: Writer>(&mut self, items: &[T])
+    where
+        Self: Sized,
+    {
+        for item in items {
+            item.write(self);
+        }
+    }
+
     fn write_be<T: Writer>(&mut self, v: &T);
 
     fn write_le<T: Writer>(&mut self, v: &T);
 
+    ///moves the bytes out of `bytes`, leaving it empty
     fn append(&mut self, bytes: &mut Vec<u8>);
 
     fn bytes(self) -> Vec<u8>;
+
+    ///takes the written bytes and compresses them with [`Compression::encode`], the usual last
+    ///step of writing a file, a [`StreamWriter`] has no bytes left to take and compresses as it
+    ///goes with [`Compression::writer`] instead
+    fn into_compressed(self, compression: Compression) -> std::io::Result<Vec<u8>>
+    where
+        Self: Sized,
+    {
+        compression.encode(self.bytes())
+    }
+
+    ///copies borrowed bytes in, sparing callers an owned `Vec`
+    fn extend(&mut self, bytes: &[u8]);
+
+    ///writes the low 3 bytes of `v` in ``big endian``, the high byte is dropped
+    fn write_u24_be(&mut self, v: u32) {
+        self.extend(&v.to_be_bytes()[1..]);
+    }
+
+    ///writes already encoded ``MUTF-8`` bytes as a length prefixed string, as is
+    fn write_raw_string_bytes(&mut self, bytes: &[u8])
+    where
+        Self: Sized,
+    {
+        write_string_len(bytes.len(), self);
+        self.extend(bytes);
+    }
+
+    ///writes an unsigned ``LEB128`` varint
+    fn write_varint(&mut self, mut v: u64) {
+        loop {
+            let b = (v & 0x7F) as u8;
+            v >>= 7;
+            if v == 0 {
+                self.extend(&[b]);
+                return;
+            }
+            self.extend(&[b | 0x80]);
+        }
+    }
+
+    ///writes the tag id, the name and the payload, the framing of a file's root tag
+    ///and the counterpart of [`crate::nbt::read_named`]
+    fn write_named_tag(&mut self, name: &str, tag: &Tag)
+    where
+        Self: Sized,
+    {
+        crate::nbt::write_named(name, tag, self)
+    }
+
+    ///reserves room for at least `additional` more bytes
+    fn reserve(&mut self, additional: usize);
+
+    ///appends zero bytes until the length is a multiple of `alignment`
+    fn pad_to(&mut self, alignment: usize);
+
+    fn options(&self) -> &WriteOptions;
+
+    fn endianness(&self) -> Endianness;
 }
 
 pub trait PrimitiveFileWriter {
@@ -259,17 +882,30 @@ pub trait PrimitiveFileWriter {
 }
 
 macro_rules! file_writer {
-    ($writer:ident, $endian:ident, $writer_inverse:ident, $endian_inverse:ident, $endian_primitive:ident) => {
+    ($writer:ident, $endianness:ident, $endian:ident, $writer_inverse:ident, $endian_inverse:ident, $endian_primitive:ident) => {
         pub struct $writer {
             bytes: Vec<u8>,
+            options: WriteOptions,
         }
 
         impl $writer {
             pub fn new() -> Self {
                 Self {
                     bytes: Vec::<u8>::new(),
+                    options: WriteOptions::default(),
                 }
             }
+
+            pub fn with_options(mut self, options: WriteOptions) -> Self {
+                self.options = options;
+                self
+            }
+        }
+
+        impl Default for $writer {
+            fn default() -> Self {
+                Self::new()
+            }
         }
 
         impl PrimitiveFileWriter for $writer {
@@ -284,7 +920,7 @@ macro_rules! file_writer {
             }
 
             fn $endian_inverse<T: Writer>(&mut self, v: &T) {
-                let mut inverse = $writer_inverse::new();
+                let mut inverse = $writer_inverse::new().with_options(self.options);
                 v.write(&mut inverse);
                 self.append(&mut inverse.bytes);
             }
@@ -296,12 +932,37 @@ macro_rules! file_writer {
             fn bytes(self) -> Vec<u8> {
                 self.bytes
             }
+
+            fn extend(&mut self, bytes: &[u8]) {
+                self.bytes.extend_from_slice(bytes);
+            }
+
+            fn reserve(&mut self, additional: usize) {
+                self.bytes.reserve(additional);
+            }
+
+            fn pad_to(&mut self, alignment: usize) {
+                if alignment == 0 {
+                    return;
+                }
+                let len = self.bytes.len().div_ceil(alignment) * alignment;
+                self.bytes.resize(len, 0x00);
+            }
+
+            fn options(&self) -> &WriteOptions {
+                &self.options
+            }
+
+            fn endianness(&self) -> Endianness {
+                Endianness::$endianness
+            }
         }
     };
 }
 
 file_writer!(
     FileWriterBE,
+    Big,
     write_be,
     FileWriterLE,
     write_le,
@@ -309,8 +970,461 @@ file_writer!(
 );
 file_writer!(
     FileWriterLE,
+    Little,
     write_le,
     FileWriterBE,
     write_be,
     primitive_write_le
 );
+
+///a writer over a borrowed fixed size buffer, for serializing without growing a `Vec`
+///
+///`Writer`s can't fail, so writing past the end of the buffer only sets a flag: everything
+///from the first write that didn't fit on is dropped and [`SliceWriter::finish`] errors
+pub struct SliceWriter<'a> {
+    bytes: &'a mut [u8],
+    pos: usize,
+    endianness: Endianness,
+    options: WriteOptions,
+    overflowed: bool,
+}
+
+impl<'a> SliceWriter<'a> {
+    pub fn new(bytes: &'a mut [u8], endianness: Endianness) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            endianness,
+            options: WriteOptions::default(),
+            overflowed: false,
+        }
+    }
+
+    pub fn with_options(mut self, options: WriteOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    ///number of bytes written so far
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    ///true once a write didn't fit the buffer
+    pub fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    ///returns the number of bytes written, erroring if the buffer was too small
+    pub fn finish(self) -> BinResult<usize> {
+        if self.overflowed {
+            return Err(BinError::UnexpectedEndOfByteStream);
+        }
+        Ok(self.pos)
+    }
+
+    fn write_with<T: Writer>(&mut self, endianness: Endianness, v: &T) {
+        let endianness = std::mem::replace(&mut self.endianness, endianness);
+        v.write(self);
+        self.endianness = endianness;
+    }
+
+    fn put(&mut self, bytes: &[u8]) {
+        if self.overflowed || bytes.len() > self.bytes.len() - self.pos {
+            self.overflowed = true;
+            return;
+        }
+        self.bytes[self.pos..self.pos + bytes.len()].copy_from_slice(bytes);
+        self.pos += bytes.len();
+    }
+}
+
+impl PrimitiveFileWriter for SliceWriter<'_> {
+    fn primitive_write<T: PrimitiveIo>(&mut self, v: &T) {
+        match self.endianness {
+            Endianness::Big => v.primitive_write_be(self),
+            Endianness::Little => v.primitive_write_le(self),
+        }
+    }
+}
+
+impl FileWriter for SliceWriter<'_> {
+    fn write_be<T: Writer>(&mut self, v: &T) {
+        self.write_with(Endianness::Big, v)
+    }
+
+    fn write_le<T: Writer>(&mut self, v: &T) {
+        self.write_with(Endianness::Little, v)
+    }
+
+    fn append(&mut self, bytes: &mut Vec<u8>) {
+        self.put(bytes);
+        bytes.clear();
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        self.put(bytes);
+    }
+
+    ///no-op, the buffer can't grow
+    fn reserve(&mut self, _additional: usize) {}
+
+    fn pad_to(&mut self, alignment: usize) {
+        if alignment == 0 {
+            return;
+        }
+        let len = self.pos.div_ceil(alignment) * alignment;
+        if len > self.bytes.len() {
+            self.overflowed = true;
+        } else if !self.overflowed {
+            self.bytes[self.pos..len].fill(0x00);
+            self.pos = len;
+        }
+    }
+
+    fn options(&self) -> &WriteOptions {
+        &self.options
+    }
+
+    fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    ///copies the written part of the buffer
+    fn bytes(self) -> Vec<u8> {
+        self.bytes[..self.pos].to_vec()
+    }
+}
+
+///bytes a [`StreamWriter`] collects before passing them on to its inner writer
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+///a writer passing its bytes on to an [`std::io::Write`] as it goes, so a large tree is never
+///held in memory serialized in full, like when piping it through [`crate::compression::CompressedWriter`]
+///
+///`Writer`s can't fail, so the first io error is kept and returned by [`StreamWriter::finish`],
+///what's written after it is dropped
+pub struct StreamWriter<W: std::io::Write> {
+    inner: W,
+    buf: Vec<u8>,
+    flushed: usize,
+    endianness: Endianness,
+    options: WriteOptions,
+    error: Option<std::io::Error>,
+}
+
+impl<W: std::io::Write> StreamWriter<W> {
+    pub fn new(inner: W, endianness: Endianness) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            flushed: 0,
+            endianness,
+            options: WriteOptions::default(),
+            error: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: WriteOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    ///number of bytes written so far, flushed or not
+    pub fn position(&self) -> usize {
+        self.flushed + self.buf.len()
+    }
+
+    ///passes the remaining bytes on and returns the inner writer, or the first io error
+    pub fn finish(mut self) -> BinResult<W> {
+        self.flush();
+        match self.error {
+            Some(e) => Err(e.into()),
+            None => Ok(self.inner),
+        }
+    }
+
+    fn write_with<T: Writer>(&mut self, endianness: Endianness, v: &T) {
+        let endianness = std::mem::replace(&mut self.endianness, endianness);
+        v.write(self);
+        self.endianness = endianness;
+    }
+
+    fn put(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+        if self.buf.len() >= STREAM_BUFFER_SIZE {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.error.is_none() {
+            if let Err(e) = self.inner.write_all(&self.buf) {
+                self.error = Some(e);
+            }
+        }
+        self.flushed += self.buf.len();
+        self.buf.clear();
+    }
+}
+
+impl<W: std::io::Write> PrimitiveFileWriter for StreamWriter<W> {
+    fn primitive_write<T: PrimitiveIo>(&mut self, v: &T) {
+        match self.endianness {
+            Endianness::Big => v.primitive_write_be(self),
+            Endianness::Little => v.primitive_write_le(self),
+        }
+    }
+}
+
+impl<W: std::io::Write> FileWriter for StreamWriter<W> {
+    fn write_be<T: Writer>(&mut self, v: &T) {
+        self.write_with(Endianness::Big, v)
+    }
+
+    fn write_le<T: Writer>(&mut self, v: &T) {
+        self.write_with(Endianness::Little, v)
+    }
+
+    fn append(&mut self, bytes: &mut Vec<u8>) {
+        self.put(bytes);
+        bytes.clear();
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        self.put(bytes);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.buf.reserve(additional.min(STREAM_BUFFER_SIZE));
+    }
+
+    fn pad_to(&mut self, alignment: usize) {
+        if alignment == 0 {
+            return;
+        }
+        let len = self.position().div_ceil(alignment) * alignment;
+        self.put(&vec![0x00; len - self.position()]);
+    }
+
+    fn options(&self) -> &WriteOptions {
+        &self.options
+    }
+
+    fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    ///passes everything on to the inner writer and returns nothing, the bytes are all in there,
+    ///[`StreamWriter::finish`] also reports io errors
+    fn bytes(mut self) -> Vec<u8> {
+        self.flush();
+        Vec::new()
+    }
+}
+
+///writes into another writer with a byte order and options of its own, for framings that
+///don't follow the writer they're given like [`crate::nbt::NbtFlavor`]s
+pub(crate) struct ReframedWriter<'a, W: FileWriter> {
+    inner: &'a mut W,
+    endianness: Endianness,
+    options: WriteOptions,
+}
+
+impl<'a, W: FileWriter> ReframedWriter<'a, W> {
+    pub(crate) fn new(inner: &'a mut W, endianness: Endianness, options: WriteOptions) -> Self {
+        Self {
+            inner,
+            endianness,
+            options,
+        }
+    }
+
+    fn write_with<T: Writer>(&mut self, endianness: Endianness, v: &T) {
+        v.write(&mut ReframedWriter::new(
+            self.inner,
+            endianness,
+            self.options,
+        ))
+    }
+}
+
+impl<W: FileWriter> PrimitiveFileWriter for ReframedWriter<'_, W> {
+    fn primitive_write<T: PrimitiveIo>(&mut self, v: &T) {
+        match self.endianness {
+            Endianness::Big => v.primitive_write_be(self),
+            Endianness::Little => v.primitive_write_le(self),
+        }
+    }
+}
+
+impl<W: FileWriter> FileWriter for ReframedWriter<'_, W> {
+    fn write_be<T: Writer>(&mut self, v: &T) {
+        self.write_with(Endianness::Big, v)
+    }
+
+    fn write_le<T: Writer>(&mut self, v: &T) {
+        self.write_with(Endianness::Little, v)
+    }
+
+    fn append(&mut self, bytes: &mut Vec<u8>) {
+        self.inner.append(bytes);
+    }
+
+    fn extend(&mut self, bytes: &[u8]) {
+        self.inner.extend(bytes);
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+    }
+
+    fn pad_to(&mut self, alignment: usize) {
+        self.inner.pad_to(alignment);
+    }
+
+    fn options(&self) -> &WriteOptions {
+        &self.options
+    }
+
+    fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    ///everything went straight into the inner writer, so this returns nothing
+    fn bytes(self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+///a reader owning its buffer, so more bytes can be fed in while it's being read,
+///like data arriving in pieces from a socket
+///
+///a parse that runs out of bytes can be retried once more data is in, see [`GrowableReader::try_read`]
+pub struct GrowableReader {
+    bytes: Vec<u8>,
+    pos: usize,
+    endianness: Endianness,
+    options: ReadOptions,
+}
+
+impl GrowableReader {
+    pub fn new(endianness: Endianness) -> Self {
+        Self {
+            bytes: Vec::new(),
+            pos: 0,
+            endianness,
+            options: ReadOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: ReadOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    ///appends bytes to the end of the buffer
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    ///drops the bytes already read, positions are relative to the new start afterwards
+    pub fn compact(&mut self) {
+        self.bytes.drain(..self.pos.min(self.bytes.len()));
+        self.pos = 0;
+    }
+
+    pub fn set_position(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    ///runs `f`, rewinding to where it started and returning `None` if it ran out of bytes
+    ///(a [`BinError::LengthExceedsInput`] included, the missing bytes may still arrive)
+    ///
+    ///any other error is returned as is, the position is then left wherever `f` failed
+    pub fn try_read<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> BinResult<T>,
+    ) -> BinResult<Option<T>> {
+        let start = self.pos;
+        match f(self) {
+            Ok(v) => Ok(Some(v)),
+            Err(BinError::UnexpectedEndOfByteStream | BinError::LengthExceedsInput { .. }) => {
+                self.pos = start;
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_with<T: Io>(&mut self, endianness: Endianness) -> BinResult<T> {
+        let endianness = std::mem::replace(&mut self.endianness, endianness);
+        let r = T::read(self);
+        self.endianness = endianness;
+        r
+    }
+}
+
+impl PrimitiveFileReader for GrowableReader {
+    fn primitive_read<T: PrimitiveIo>(&mut self) -> BinResult<T> {
+        match self.endianness {
+            Endianness::Big => T::primitive_read_be(self),
+            Endianness::Little => T::primitive_read_le(self),
+        }
+    }
+}
+
+impl FileReader for GrowableReader {
+    fn read_be<T: Io>(&mut self) -> BinResult<T> {
+        self.read_with(Endianness::Big)
+    }
+
+    fn read_le<T: Io>(&mut self) -> BinResult<T> {
+        self.read_with(Endianness::Little)
+    }
+
+    fn get_slice(&mut self, len: usize) -> BinResult<&[u8]> {
+        let end = match self.pos.checked_add(len) {
+            Some(end) if end <= self.bytes.len() => end,
+            _ => return Err(BinError::UnexpectedEndOfByteStream),
+        };
+        let start = std::mem::replace(&mut self.pos, end);
+        Ok(&self.bytes[start..end])
+    }
+
+    fn align_to(&mut self, alignment: usize) -> BinResult<()> {
+        if alignment == 0 {
+            return Ok(());
+        }
+        let pos = self.pos.div_ceil(alignment) * alignment;
+        if pos > self.bytes.len() {
+            return Err(BinError::UnexpectedEndOfByteStream);
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    fn rest(&self) -> Vec<u8> {
+        self.bytes[self.pos.min(self.bytes.len())..].to_owned()
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn buffer(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn options(&self) -> &ReadOptions {
+        &self.options
+    }
+
+    fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+}
@@ -1,33 +1,188 @@
-use flate2::read::{GzDecoder, ZlibDecoder};
-use flate2::write::{GzEncoder, ZlibEncoder};
+use crate::binary::{BinError, BinResult};
+use crate::nbt::validate_tag_id;
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+use flate2::Crc;
 use std::io::{Read, Write};
+use std::ops::Range;
 
 pub const GZIP_MAGIC_NUMBER: [u8; 2] = [0x1F, 0x8B];
 pub const ZLIB_MAGIC_NUMBER: [u8; 1] = [0x78];
+///set in a region chunk's compression byte when its data lives in a separate `c.X.Z.mcc` file
+pub const EXTERNAL_CHUNK_FLAG: u8 = 0x80;
 
+///byte counts of a [`Compression::decode_with_stats`] call
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DecodeStats {
+    pub input_len: usize,
+    pub output_len: usize,
+}
+
+impl DecodeStats {
+    ///decompressed size divided by compressed size, `0.0` for empty input
+    pub fn ratio(&self) -> f64 {
+        if self.input_len == 0 {
+            return 0.0;
+        }
+        self.output_len as f64 / self.input_len as f64
+    }
+}
+
+///gzip header written by [`Encoder`]: no flags, no mtime, unknown OS
+const GZIP_HEADER: [u8; 10] = [0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Compression {
     Uncompressed,
     GZIP,
     ZLIB,
 }
 
+///what [`Compression::smart_decode`] decoded a buffer with
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SmartCompression {
+    Known(Compression),
+    ///deflate without a gzip or zlib wrapper, which has no [`Compression`] variant
+    RawDeflate,
+}
+
 impl Compression {
+    ///guesses the compression of `buf` from its magic number
+    ///
+    ///uncompressed NBT is only recognized by a leading tag id (`0x01..=0x0C`, a root compound
+    ///being `0x0A`), anything else, empty buffers included, is `None`
+    pub fn detect(buf: &[u8]) -> Option<Compression> {
+        if buf.starts_with(&GZIP_MAGIC_NUMBER) {
+            Some(Compression::GZIP)
+        } else if buf.starts_with(&ZLIB_MAGIC_NUMBER) {
+            Some(Compression::ZLIB)
+        } else if starts_with_tag_id(buf) {
+            Some(Compression::Uncompressed)
+        } else {
+            None
+        }
+    }
+
     pub fn decode(&self, buf: Vec<u8>) -> std::io::Result<Vec<u8>> {
         match self {
             Compression::Uncompressed => Ok(buf),
+            _ => self.decode_slice(&buf),
+        }
+    }
+
+    ///decodes `buf[range]` straight out of the larger buffer, like a chunk inside a region file
+    ///
+    ///compressed input is streamed from the slice without being copied first, only
+    ///uncompressed input is copied into the returned `Vec`, a range outside of `buf` errors
+    pub fn decode_range(&self, buf: &[u8], range: Range<usize>) -> std::io::Result<Vec<u8>> {
+        let (start, end) = (range.start, range.end);
+        match buf.get(range) {
+            Some(buf) => self.decode_slice(buf),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Range {}..{} is outside of the {} byte buffer",
+                    start,
+                    end,
+                    buf.len()
+                ),
+            )),
+        }
+    }
+
+    ///decodes `buf` of unknown compression, returning the compression that worked
+    ///
+    ///the [`Compression::detect`]ed compression is tried first and then every other one,
+    ///the first to decode into a buffer starting with a tag id wins, so misleading magic
+    ///numbers don't fail the read, raw deflate is tried last, errors with the first decoding
+    ///error otherwise
+    pub fn smart_decode(buf: &[u8]) -> std::io::Result<(SmartCompression, Vec<u8>)> {
+        let detected = Compression::detect(buf);
+        let mut order = [
+            Compression::GZIP,
+            Compression::ZLIB,
+            Compression::Uncompressed,
+        ];
+        order.sort_by_key(|&c| Some(c) != detected);
+        let mut error = None;
+        for compression in order {
+            match compression.decode_slice(buf) {
+                Ok(data) if starts_with_tag_id(&data) => {
+                    return Ok((SmartCompression::Known(compression), data))
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        let mut data = vec![];
+        match DeflateDecoder::new(buf).read_to_end(&mut data) {
+            Ok(_) if starts_with_tag_id(&data) => return Ok((SmartCompression::RawDeflate, data)),
+            Ok(_) => {}
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+        Err(error.unwrap_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "No compression decodes the buffer into NBT",
+            )
+        }))
+    }
+
+    ///decodes `buf` and reports its compressed and decompressed sizes
+    pub fn decode_with_stats(&self, buf: &[u8]) -> std::io::Result<(Vec<u8>, DecodeStats)> {
+        let data = self.decode_slice(buf)?;
+        let stats = DecodeStats {
+            input_len: buf.len(),
+            output_len: data.len(),
+        };
+        Ok((data, stats))
+    }
+
+    pub(crate) fn decode_slice(&self, buf: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compression::Uncompressed => Ok(buf.to_vec()),
             Compression::GZIP => {
                 let mut data = vec![];
-                GzDecoder::new(&buf[..]).read_to_end(&mut data)?;
+                GzDecoder::new(buf)
+                    .read_to_end(&mut data)
+                    .map_err(|e| self.decode_error(buf, e))?;
                 Ok(data)
             }
             Compression::ZLIB => {
                 let mut data = vec![];
-                ZlibDecoder::new(&buf[..]).read_to_end(&mut data)?;
+                ZlibDecoder::new(buf)
+                    .read_to_end(&mut data)
+                    .map_err(|e| self.decode_error(buf, e))?;
                 Ok(data)
             }
         }
     }
 
+    ///adds the declared compression, the first bytes of `buf` and the compression they
+    ///look like to a decompression error, keeping its kind
+    fn decode_error(&self, buf: &[u8], e: std::io::Error) -> std::io::Error {
+        let start: Vec<_> = buf.iter().take(4).map(|b| format!("0x{:02X}", b)).collect();
+        let looks_like = match Compression::detect(buf) {
+            Some(Compression::Uncompressed) => "uncompressed NBT".to_string(),
+            Some(compression) if compression != *self => compression.as_str().to_string(),
+            _ => "damaged or truncated data".to_string(),
+        };
+        std::io::Error::new(
+            e.kind(),
+            format!(
+                "Expected {} but the buffer starts with [{}] (looks like {}): {}",
+                self.as_str(),
+                start.join(", "),
+                looks_like,
+                e
+            ),
+        )
+    }
+
     pub fn encode(&self, buf: Vec<u8>) -> std::io::Result<Vec<u8>> {
         match self {
             Compression::Uncompressed => Ok(buf),
@@ -44,6 +199,27 @@ impl Compression {
         }
     }
 
+    ///an [`Encoder`] that keeps its compressor state between calls, for encoding many buffers
+    pub fn encoder(&self) -> Encoder {
+        let level = flate2::Compression::default();
+        Encoder(match self {
+            Compression::Uncompressed => EncoderState::Uncompressed,
+            Compression::GZIP => EncoderState::Gzip(DeflateEncoder::new(Vec::new(), level)),
+            Compression::ZLIB => EncoderState::Zlib(ZlibEncoder::new(Vec::new(), level)),
+        })
+    }
+
+    ///wraps `inner` so everything written to the returned writer reaches it compressed,
+    ///the streaming counterpart of [`Compression::encode`] producing the same bytes
+    pub fn writer<W: Write>(&self, inner: W) -> CompressedWriter<W> {
+        let level = flate2::Compression::default();
+        CompressedWriter(match self {
+            Compression::Uncompressed => WriterState::Uncompressed(inner),
+            Compression::GZIP => WriterState::Gzip(GzEncoder::new(inner, level)),
+            Compression::ZLIB => WriterState::Zlib(ZlibEncoder::new(inner, level)),
+        })
+    }
+
     pub fn as_str(&self) -> &str {
         match &self {
             Compression::Uncompressed => "uncompressed",
@@ -59,4 +235,112 @@ impl Compression {
             Compression::ZLIB => &ZLIB_MAGIC_NUMBER,
         }
     }
+
+    ///parses the compression byte of a region chunk, 1 gzip, 2 zlib and 3 uncompressed,
+    ///along with whether [`EXTERNAL_CHUNK_FLAG`] is set
+    pub fn from_scheme_byte(b: u8) -> BinResult<(Compression, bool)> {
+        let compression = match b & !EXTERNAL_CHUNK_FLAG {
+            1 => Compression::GZIP,
+            2 => Compression::ZLIB,
+            3 => Compression::Uncompressed,
+            x => return Err(BinError::UnknownCompressionScheme(x)),
+        };
+        Ok((compression, b & EXTERNAL_CHUNK_FLAG != 0))
+    }
+
+    ///the inverse of [`Compression::from_scheme_byte`]
+    pub fn to_scheme_byte(&self, external: bool) -> u8 {
+        let scheme = match self {
+            Compression::GZIP => 1,
+            Compression::ZLIB => 2,
+            Compression::Uncompressed => 3,
+        };
+        if external {
+            scheme | EXTERNAL_CHUNK_FLAG
+        } else {
+            scheme
+        }
+    }
+}
+
+///whether `buf` starts like uncompressed NBT, with an id [`validate_tag_id`] accepts
+fn starts_with_tag_id(buf: &[u8]) -> bool {
+    buf.first()
+        .is_some_and(|&id| validate_tag_id(id, 0).is_ok())
+}
+
+///reusable counterpart of [`Compression::encode`]
+///
+///the compressor is reset rather than rebuilt after every buffer, which saves reallocating
+///its internal state when encoding a lot of small buffers like the chunks of a region
+pub struct Encoder(EncoderState);
+
+enum EncoderState {
+    Uncompressed,
+    Gzip(DeflateEncoder<Vec<u8>>),
+    Zlib(ZlibEncoder<Vec<u8>>),
+}
+
+impl Encoder {
+    pub fn encode(&mut self, buf: &[u8]) -> std::io::Result<Vec<u8>> {
+        match &mut self.0 {
+            EncoderState::Uncompressed => Ok(buf.to_vec()),
+            EncoderState::Gzip(encoder) => {
+                encoder.write_all(buf)?;
+                let deflated = encoder.reset(Vec::new())?;
+                let mut crc = Crc::new();
+                crc.update(buf);
+                let mut data = Vec::with_capacity(GZIP_HEADER.len() + deflated.len() + 8);
+                data.extend_from_slice(&GZIP_HEADER);
+                data.extend_from_slice(&deflated);
+                data.extend_from_slice(&crc.sum().to_le_bytes());
+                data.extend_from_slice(&crc.amount().to_le_bytes());
+                Ok(data)
+            }
+            EncoderState::Zlib(encoder) => {
+                encoder.write_all(buf)?;
+                encoder.reset(Vec::new())
+            }
+        }
+    }
+}
+
+///a writer compressing what it's given into another writer, made by [`Compression::writer`]
+///
+///[`CompressedWriter::finish`] has to be called to write the end of the compressed stream
+pub struct CompressedWriter<W: Write>(WriterState<W>);
+
+enum WriterState<W: Write> {
+    Uncompressed(W),
+    Gzip(GzEncoder<W>),
+    Zlib(ZlibEncoder<W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    ///writes the rest of the compressed stream and returns the inner writer
+    pub fn finish(self) -> std::io::Result<W> {
+        match self.0 {
+            WriterState::Uncompressed(inner) => Ok(inner),
+            WriterState::Gzip(encoder) => encoder.finish(),
+            WriterState::Zlib(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.0 {
+            WriterState::Uncompressed(inner) => inner.write(buf),
+            WriterState::Gzip(encoder) => encoder.write(buf),
+            WriterState::Zlib(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.0 {
+            WriterState::Uncompressed(inner) => inner.flush(),
+            WriterState::Gzip(encoder) => encoder.flush(),
+            WriterState::Zlib(encoder) => encoder.flush(),
+        }
+    }
 }
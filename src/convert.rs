@@ -0,0 +1,125 @@
+use crate::binary::{BinError, BinResult};
+use crate::nbt::{
+    Compound, List, Tag, TAG_BYTE, TAG_COMPOUND, TAG_DOUBLE, TAG_FLOAT, TAG_INT, TAG_LIST,
+    TAG_LONG, TAG_SHORT, TAG_STRING,
+};
+
+///builds a value out of a tag, the hand written counterpart of a derive
+///
+///implementations usually pull their fields with the `get_required_*`/`get_optional_*` helpers of [`Tag`]
+pub trait ReadFrom: Sized {
+    fn read_from(tag: &Tag) -> BinResult<Self>;
+}
+
+///turns a value into a tag, the inverse of [`ReadFrom`]
+pub trait WriteTo {
+    fn write_to(&self) -> Tag;
+}
+
+macro_rules! impl_primitive {
+    ($ty:ty, $variant:ident, $id:expr) => {
+        impl ReadFrom for $ty {
+            fn read_from(tag: &Tag) -> BinResult<Self> {
+                match tag {
+                    Tag::$variant(v) => Ok(v.clone()),
+                    tag => Err(BinError::UnexpectedTagType {
+                        expected: $id,
+                        found: tag.tag_id(),
+                    }),
+                }
+            }
+        }
+
+        impl WriteTo for $ty {
+            fn write_to(&self) -> Tag {
+                Tag::$variant(self.clone())
+            }
+        }
+    };
+}
+
+impl_primitive!(i8, Byte, TAG_BYTE);
+impl_primitive!(i16, Short, TAG_SHORT);
+impl_primitive!(i32, Int, TAG_INT);
+impl_primitive!(i64, Long, TAG_LONG);
+impl_primitive!(f32, Float, TAG_FLOAT);
+impl_primitive!(f64, Double, TAG_DOUBLE);
+impl_primitive!(String, String, TAG_STRING);
+
+macro_rules! getters {
+    ($required:ident, $optional:ident, $variant:ident, $id:expr, $ty:ty, $v:ident => $get:expr) => {
+        ///gets a field of a compound, erroring if it's missing or of another type
+        pub fn $required(&self, key: &str) -> BinResult<$ty> {
+            self.$optional(key)?.ok_or_else(|| missing_key(key))
+        }
+
+        ///gets a field of a compound, `None` if it's missing, erroring if it's of another type
+        pub fn $optional(&self, key: &str) -> BinResult<Option<$ty>> {
+            match self.field(key)? {
+                None => Ok(None),
+                Some(Tag::$variant($v)) => Ok(Some($get)),
+                Some(tag) => Err(mismatched_field(key, $id, tag)),
+            }
+        }
+    };
+}
+
+impl Tag {
+    getters!(get_required_i8, get_optional_i8, Byte, TAG_BYTE, i8, v => *v);
+    getters!(get_required_i16, get_optional_i16, Short, TAG_SHORT, i16, v => *v);
+    getters!(get_required_i32, get_optional_i32, Int, TAG_INT, i32, v => *v);
+    getters!(get_required_i64, get_optional_i64, Long, TAG_LONG, i64, v => *v);
+    getters!(get_required_f32, get_optional_f32, Float, TAG_FLOAT, f32, v => *v);
+    getters!(get_required_f64, get_optional_f64, Double, TAG_DOUBLE, f64, v => *v);
+    getters!(get_required_string, get_optional_string, String, TAG_STRING, &str, v => v);
+    getters!(get_required_list, get_optional_list, List, TAG_LIST, &List, v => v);
+    getters!(get_required_compound, get_optional_compound, Compound, TAG_COMPOUND, &Compound, v => v);
+
+    ///reads a field of a compound with its [`ReadFrom`] implementation, erroring if it's missing
+    pub fn get_required<T: ReadFrom>(&self, key: &str) -> BinResult<T> {
+        self.get_optional(key)?.ok_or_else(|| missing_key(key))
+    }
+
+    ///reads a field of a compound with its [`ReadFrom`] implementation, `None` if it's missing
+    ///
+    ///errors from `T` are prefixed with the key so nested failures point at the field
+    pub fn get_optional<T: ReadFrom>(&self, key: &str) -> BinResult<Option<T>> {
+        match self.field(key)? {
+            None => Ok(None),
+            Some(tag) => T::read_from(tag)
+                .map(Some)
+                .map_err(|e| e.context(format!("{:?}", key))),
+        }
+    }
+
+    ///parses a string field of a compound as JSON, like a text component stored in NBT
+    ///
+    ///`None` if the tag isn't a compound or the field is missing, isn't a string or isn't valid JSON
+    #[cfg(feature = "serde")]
+    pub fn parse_json_string_field(&self, key: &str) -> Option<serde_json::Value> {
+        serde_json::from_str(self.get_optional_string(key).ok()??).ok()
+    }
+
+    fn field(&self, key: &str) -> BinResult<Option<&Tag>> {
+        match self {
+            Tag::Compound(map) => Ok(map.get(key)),
+            tag => Err(BinError::UnexpectedTagType {
+                expected: TAG_COMPOUND,
+                found: tag.tag_id(),
+            }
+            .context(format!("Getting {:?}", key))),
+        }
+    }
+}
+
+fn missing_key(key: &str) -> BinError {
+    BinError::MissingKey(key.to_string())
+}
+
+fn mismatched_field(key: &str, expected: u8, found: &Tag) -> BinError {
+    BinError::UnexpectedTagType {
+        expected,
+        found: found.tag_id(),
+    }
+    .context(format!("{:?}", key))
+}
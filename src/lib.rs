@@ -1,18 +1,23 @@
 pub mod binary;
 pub mod compression;
+pub mod convert;
 pub mod nbt;
+pub mod prelude;
+pub mod region;
+pub mod snbt;
+pub mod tree;
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bin::FileReader;
+    use bin::{FileReader, FileWriter};
     use binary as bin;
     //allows for reading/writing tag payloads with read_be & write_be
     use bin::TagIo;
 
     #[test]
     fn read_u8() {
-        let x = &vec![0x01];
+        let x = &[0x01];
         let mut fr = bin::FileReaderBE::new(x, 0);
         let byte = fr.read::<u8>().unwrap();
         assert_eq!(byte, 0x01);
@@ -21,7 +26,7 @@ mod tests {
     #[test]
     fn read_be_byte_tag() {
         //---payloads---//
-        let x = &vec![0x00, 0x01, 0x02, 0x03];
+        let x = &[0x00, 0x01, 0x02, 0x03];
         let tag_id = 0x01;
         let mut fr = bin::FileReaderBE::new(x, 0);
         assert_eq!(
@@ -45,7 +50,7 @@ mod tests {
     #[test]
     fn read_le_byte_tag() {
         //---payloads---//
-        let x = &vec![0x00, 0x01, 0x02, 0x03];
+        let x = &[0x00, 0x01, 0x02, 0x03];
         let tag_id = 0x01;
         let mut fr = bin::FileReaderLE::new(x, 0);
         assert_eq!(
@@ -70,7 +75,7 @@ mod tests {
     fn read_be_short_tag() {
         {
             //---payloads---//
-            let x = &vec![0x00, 0x04, 0x01, 0x05, 0x02, 0x06, 0x03, 0x07];
+            let x = &[0x00, 0x04, 0x01, 0x05, 0x02, 0x06, 0x03, 0x07];
             let tag_id = 0x02;
             let mut fr = bin::FileReaderBE::new(x, 0);
             assert_eq!(
@@ -95,7 +100,7 @@ mod tests {
     #[test]
     fn read_le_short_tag() {
         //---payloads---//
-        let x = &vec![0x00, 0x04, 0x01, 0x05, 0x02, 0x06, 0x03, 0x07];
+        let x = &[0x00, 0x04, 0x01, 0x05, 0x02, 0x06, 0x03, 0x07];
         let tag_id = 0x02;
         let mut fr = bin::FileReaderLE::new(x, 0);
         assert_eq!(
@@ -115,4 +120,2693 @@ mod tests {
             nbt::Tag::Short(0x0703)
         );
     }
+
+    #[test]
+    fn region_timestamp() {
+        let mut bytes = vec![0x00; region::HEADER_SIZE];
+        //chunk (1, 0) at sector 2 spanning 1 sector
+        bytes[4..8].copy_from_slice(&[0x00, 0x00, 0x02, 0x01]);
+        let mut region = region::RegionFile::from_bytes(bytes).unwrap();
+        assert_eq!(region.timestamp(1, 0), Some(0));
+        assert_eq!(region.timestamp(0, 0), None);
+        assert_eq!(region.timestamp(32, 0), None);
+
+        region.set_timestamp(1, 0, 1_700_000_000).unwrap();
+        assert_eq!(region.timestamp(1, 0), Some(1_700_000_000));
+        assert!(matches!(
+            region.set_timestamp(0, 32, 0),
+            Err(bin::BinError::ChunkOutOfRegion { x: 0, z: 32 })
+        ));
+    }
+
+    #[test]
+    fn region_external_chunk() {
+        let dir = std::env::temp_dir().join("mm_io_region_external_chunk");
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut bytes = vec![0x00; region::HEADER_SIZE + region::SECTOR_SIZE];
+        //chunk (1, 0) at sector 2, zlib compressed and stored externally
+        bytes[4..8].copy_from_slice(&[0x00, 0x00, 0x02, 0x01]);
+        bytes[region::HEADER_SIZE..region::HEADER_SIZE + 5]
+            .copy_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x82]);
+        std::fs::write(dir.join("r.0.0.mca"), bytes).unwrap();
+        let _ = std::fs::remove_file(dir.join("c.1.0.mcc"));
+
+        let region = region::RegionFile::open(dir.join("r.0.0.mca")).unwrap();
+        let missing = region.read_chunk(1, 0).unwrap_err();
+        assert!(matches!(
+            missing.root_cause(),
+            bin::BinError::MissingExternalChunk { path } if path.ends_with("c.1.0.mcc")
+        ));
+        assert_eq!(region.read_chunk(0, 0).unwrap(), None);
+
+        let chunk = vec![
+            0x0A, 0x00, 0x00, 0x03, 0x00, 0x01, b'x', 0x00, 0x00, 0x00, 0x05, 0x00,
+        ];
+        let chunk = compression::Compression::ZLIB.encode(chunk).unwrap();
+        std::fs::write(dir.join("c.1.0.mcc"), chunk).unwrap();
+        assert_eq!(
+            region.read_chunk(1, 0).unwrap(),
+            Some(nbt::Tag::Int(5).wrapped("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn write_all_tags() {
+        let tags = [nbt::Tag::Byte(0x01), nbt::Tag::Short(0x0203)];
+        let mut fw = bin::FileWriterBE::new();
+        fw.write_all(&tags);
+        fw.write(&&tags[0]);
+        assert_eq!(fw.bytes(), vec![0x01, 0x02, 0x03, 0x01]);
+    }
+
+    #[test]
+    fn region_write_chunk() {
+        let mut region = region::RegionFile::new();
+        let chunk = nbt::Tag::Int(5).wrapped("x".to_string());
+        for compression in [
+            compression::Compression::GZIP,
+            compression::Compression::ZLIB,
+            compression::Compression::Uncompressed,
+        ] {
+            region.write_chunk(3, 4, &chunk, compression).unwrap();
+            assert_eq!(region.read_chunk(3, 4).unwrap(), Some(chunk.clone()));
+        }
+        assert_eq!(region.location(3, 4), Some((2, 1)));
+        assert!(region.timestamp(3, 4).unwrap() > 0);
+
+        let region = region::RegionFile::from_bytes(region.bytes()).unwrap();
+        assert_eq!(region.read_chunk(3, 4).unwrap(), Some(chunk));
+    }
+
+    #[test]
+    fn tag_as_ref() {
+        let tag = nbt::Tag::IntArray(vec![1, 2, 3]);
+        assert_eq!(tag.as_ref(), nbt::TagRef::IntArray(&[1, 2, 3]));
+        assert_eq!(tag.as_ref().to_tag(), tag);
+
+        let tag = nbt::Tag::String("abc".to_string()).wrapped("s".to_string());
+        match tag.as_ref() {
+            nbt::TagRef::Compound(map) => assert_eq!(map["s"].as_ref(), nbt::TagRef::String("abc")),
+            _ => panic!("expected a compound"),
+        }
+    }
+
+    #[test]
+    fn roundtrip_sample_files() {
+        use compression::Compression;
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data");
+        let mut processed = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|e| e == "md") {
+                continue;
+            }
+            let bytes = std::fs::read(&path).unwrap();
+            let compression = Compression::detect(&bytes).unwrap();
+            let bytes = compression.decode(bytes).unwrap();
+            let mut fr = bin::FileReaderBE::new(&bytes, 0);
+            let tag_id = bytes[0];
+            let (name, tag) = nbt::read_named(&mut fr).unwrap();
+            assert!(fr.at_end(), "{} has trailing bytes", path.display());
+
+            //compound key order isn't preserved, so compare the parsed trees instead of bytes
+            let mut fw = bin::FileWriterBE::new();
+            nbt::write_named(&name, &tag, &mut fw);
+            let written = compression.encode(fw.bytes()).unwrap();
+            let written = compression.decode(written).unwrap();
+            let mut fr = bin::FileReaderBE::new(&written, 0);
+            assert_eq!(fr.read::<u8>().unwrap(), tag_id);
+            assert_eq!(fr.read::<String>().unwrap(), name);
+            assert_eq!(
+                nbt::Tag::read(tag_id, &mut fr).unwrap(),
+                tag,
+                "{} didn't round-trip",
+                path.display()
+            );
+            assert!(fr.at_end());
+            processed += 1;
+        }
+        assert!(processed >= 3, "only {} sample files in {}", processed, dir);
+    }
+
+    #[test]
+    fn compound_entry() {
+        let mut tag = nbt::Tag::Compound(nbt::Compound::new());
+        tag.entry("count").unwrap().or_insert(nbt::Tag::Int(0));
+        let count = tag.entry("count").unwrap().or_insert(nbt::Tag::Int(0));
+        if let nbt::Tag::Int(count) = count {
+            *count += 1;
+        }
+        assert_eq!(tag, nbt::Tag::Int(1).wrapped("count".to_string()));
+        assert!(nbt::Tag::Byte(0).entry("count").is_none());
+    }
+
+    #[test]
+    fn decode_stats() {
+        let data = vec![0x00; 1024];
+        let encoded = compression::Compression::GZIP.encode(data.clone()).unwrap();
+        let (decoded, stats) = compression::Compression::GZIP
+            .decode_with_stats(&encoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+        assert_eq!(stats.input_len, encoded.len());
+        assert_eq!(stats.output_len, 1024);
+        assert!(stats.ratio() > 1.0);
+    }
+
+    #[test]
+    fn item_components() {
+        let mut item = nbt::Tag::String("minecraft:stick".to_string()).wrapped("id".to_string());
+        let data = nbt::Tag::Int(1).wrapped("level".to_string());
+        assert_eq!(item.get_namespaced("custom_data"), None);
+        assert_eq!(item.insert_namespaced("custom_data", data.clone()), None);
+        assert_eq!(item.get_namespaced("minecraft:custom_data"), Some(&data));
+        assert_eq!(item.get_namespaced("custom_data"), Some(&data));
+        assert_eq!(item.remove_namespaced("custom_data"), Some(data));
+        assert_eq!(item.get_namespaced("custom_data"), None);
+    }
+
+    #[test]
+    fn nested_list_helpers() {
+        let mut lists = nbt::List::Empty;
+        lists.push_list(nbt::List::Int(vec![1, 2])).unwrap();
+        lists.push_list(nbt::List::Empty).unwrap();
+        assert_eq!(
+            lists.get(0),
+            Some(nbt::TagRef::List(&nbt::List::Int(vec![1, 2])))
+        );
+        assert!(lists.push_compound(nbt::Compound::new()).is_err());
+
+        let entity = nbt::Tag::Int(1).wrapped("id".to_string());
+        let compounds =
+            nbt::List::from_compound_tags(vec![entity.clone(), entity.clone()]).unwrap();
+        assert_eq!(compounds.len(), 2);
+        assert!(compounds.iter().all(|e| e == entity.as_ref()));
+        assert!(nbt::List::from_compound_tags(vec![nbt::Tag::Byte(0)]).is_err());
+        assert!(nbt::List::from_list_tags(vec![entity]).is_err());
+    }
+
+    #[test]
+    fn alignment() {
+        let mut fw = bin::FileWriterBE::new();
+        fw.write(&0x01u8);
+        fw.pad_to(4);
+        fw.write(&0x02u8);
+        fw.pad_to(4);
+        fw.pad_to(4);
+        let bytes = fw.bytes();
+        assert_eq!(bytes, vec![0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00]);
+
+        let mut fr = bin::FileReaderBE::new(&bytes, 0);
+        fr.align_to(4).unwrap();
+        assert_eq!(fr.read::<u8>().unwrap(), 0x01);
+        fr.align_to(4).unwrap();
+        assert_eq!(fr.read::<u8>().unwrap(), 0x02);
+        assert!(fr.align_to(16).is_err());
+        fr.align_to(4).unwrap();
+        assert!(fr.at_end());
+    }
+
+    #[test]
+    fn raw_string_bytes() {
+        //U+0000 is encoded as 0xC0 0x80 in MUTF-8
+        let mut fw = bin::FileWriterLE::new();
+        fw.write_raw_string_bytes(&[b'a', 0xC0, 0x80]);
+        let bytes = fw.bytes();
+        assert_eq!(bytes, vec![0x03, 0x00, b'a', 0xC0, 0x80]);
+
+        let mut fr = bin::FileReaderLE::new(&bytes, 0);
+        assert_eq!(fr.read_raw_string_bytes().unwrap(), &[b'a', 0xC0, 0x80]);
+        assert!(fr.at_end());
+    }
+
+    #[test]
+    fn playerdata_dir() {
+        let dir = std::env::temp_dir().join("mm_io_playerdata_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let player = vec![0x0A, 0x00, 0x00, 0x01, 0x00, 0x01, b'x', 0x07, 0x00];
+        let player = compression::Compression::GZIP.encode(player).unwrap();
+        std::fs::write(dir.join("069a79f4-44e9-4726-a5be-fca90e38aaf5.dat"), player).unwrap();
+        std::fs::write(dir.join("00000000-0000-0000-0000-000000000001.dat"), []).unwrap();
+        std::fs::write(dir.join("not-a-uuid.dat"), [0x00]).unwrap();
+
+        let mut players: Vec<_> = nbt::read_playerdata_dir(&dir).unwrap().collect();
+        players.sort_by_key(|(uuid, _)| *uuid);
+        assert_eq!(players.len(), 2);
+        assert_eq!(players[0].0, 1);
+        assert!(players[0].1.is_err());
+        assert_eq!(players[1].0, 0x069a79f444e94726a5befca90e38aaf5);
+        assert_eq!(
+            *players[1].1.as_ref().unwrap(),
+            nbt::Tag::Byte(7).wrapped("x".to_string())
+        );
+    }
+
+    #[test]
+    fn flavors() {
+        use nbt::NbtFlavor;
+        let tag = nbt::Tag::Short(0x0102).wrapped("s".to_string());
+        let named_le = vec![
+            0x0A, 0x01, 0x00, b'r', 0x02, 0x01, 0x00, b's', 0x02, 0x01, 0x00,
+        ];
+        let unnamed_be = vec![0x0A, 0x02, 0x00, 0x01, b's', 0x01, 0x02, 0x00];
+
+        let mut fw = bin::FileWriterBE::new();
+        tag.write_with(NbtFlavor::BedrockDisk, "r", &mut fw)
+            .unwrap();
+        assert_eq!(fw.bytes(), named_le);
+        let mut fw = bin::FileWriterLE::new();
+        tag.write_with(NbtFlavor::JavaNetwork, "r", &mut fw)
+            .unwrap();
+        assert_eq!(fw.bytes(), unnamed_be);
+        let mut fw = bin::FileWriterBE::new();
+        tag.write_with(NbtFlavor::BedrockNetwork, "", &mut fw)
+            .unwrap();
+        assert_eq!(
+            fw.bytes(),
+            vec![0x0A, 0x00, 0x02, 0x01, b's', 0x02, 0x01, 0x00]
+        );
+
+        let mut fr = bin::FileReaderBE::new(&named_le, 0);
+        let root = nbt::Tag::read_with(NbtFlavor::BedrockDisk, &mut fr).unwrap();
+        assert_eq!(root, ("r".to_string(), tag.clone()));
+        assert!(fr.at_end());
+        let mut fr = bin::FileReaderLE::new(&unnamed_be, 0);
+        let root = nbt::Tag::read_with(NbtFlavor::JavaNetwork, &mut fr).unwrap();
+        assert_eq!(root, (String::new(), tag));
+    }
+
+    #[test]
+    fn read_raw_subtree() {
+        let x = &[0xFF, 0x01, 0x00, 0x01, b'b', 0x05, 0x00, 0xFF];
+        let mut fr = bin::FileReaderBE::new(x, 1);
+        let (tag, raw) = nbt::Tag::read_raw(0x0A, &mut fr).unwrap();
+        assert_eq!(tag, nbt::Tag::Byte(5).wrapped("b".to_string()));
+        assert_eq!(raw, x[1..7].to_vec());
+        assert_eq!(fr.position(), 7);
+    }
+
+    #[test]
+    fn collect_compound() {
+        let mut tag: nbt::Tag = vec![("a".to_string(), nbt::Tag::Byte(1))]
+            .into_iter()
+            .collect();
+        tag.extend([("b".to_string(), nbt::Tag::Byte(2))]);
+        let mut expected = nbt::Compound::new();
+        expected.insert("a".to_string(), nbt::Tag::Byte(1));
+        expected.insert("b".to_string(), nbt::Tag::Byte(2));
+        assert_eq!(tag, nbt::Tag::Compound(expected));
+    }
+
+    #[test]
+    #[should_panic]
+    fn extend_non_compound() {
+        nbt::Tag::Byte(0).extend([("a".to_string(), nbt::Tag::Byte(1))]);
+    }
+
+    #[test]
+    fn duplicate_compound_keys() {
+        let x = &[
+            0x01, 0x00, 0x01, b'a', 0x01, 0x01, 0x00, 0x01, b'a', 0x02, 0x00,
+        ];
+        let mut fr = bin::FileReaderBE::new(x, 0);
+        assert_eq!(
+            nbt::Tag::read(0x0A, &mut fr).unwrap(),
+            nbt::Tag::Byte(2).wrapped("a".to_string())
+        );
+        let strict = bin::ReadOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let mut fr = bin::FileReaderBE::new(x, 0).with_options(strict);
+        assert!(matches!(
+            nbt::Tag::read(0x0A, &mut fr),
+            Err(bin::BinError::DuplicateKey(key)) if key == "a"
+        ));
+    }
+
+    #[test]
+    fn array_with_capacity() {
+        let mut tag = nbt::Tag::long_array_with_capacity(256);
+        match &mut tag {
+            nbt::Tag::LongArray(v) => {
+                assert!(v.capacity() >= 256);
+                v.extend(0..256);
+            }
+            _ => panic!("expected a long array"),
+        }
+        let mut fw = bin::FileWriterBE::new();
+        fw.write(&tag);
+        assert_eq!(fw.bytes().len(), 4 + 256 * 8);
+    }
+
+    #[test]
+    fn prelude_imports() {
+        use crate::prelude::*;
+        let mut fw = FileWriterBE::new();
+        fw.write(&Tag::Int(7));
+        let bytes = fw.bytes();
+        let mut fr = FileReaderBE::new(&bytes, 0);
+        assert_eq!(Tag::read(0x03, &mut fr).unwrap(), Tag::Int(7));
+    }
+
+    #[test]
+    fn numbers() {
+        let sum: f64 = [nbt::Tag::Byte(1), nbt::Tag::Short(2), nbt::Tag::Double(0.5)]
+            .iter()
+            .filter_map(|t| t.as_number())
+            .map(|n| n.to_f64())
+            .sum();
+        assert_eq!(sum, 3.5);
+        assert_eq!(nbt::Tag::String("1".to_string()).as_number(), None);
+        assert_eq!(nbt::Number::Float(3.0).to_i64_checked(), Some(3));
+        assert_eq!(nbt::Number::Float(3.5).to_i64_checked(), None);
+        assert_eq!(nbt::Number::Double(f64::NAN).to_i64_checked(), None);
+        assert_eq!(nbt::Number::Double(9.3e18).to_i64_checked(), None);
+        assert_eq!(nbt::Number::Long(-5).to_tag(), nbt::Tag::Long(-5));
+    }
+
+    #[test]
+    fn write_payload() {
+        let tag = nbt::Tag::Compound(
+            [
+                ("a".to_string(), nbt::Tag::IntArray(vec![1, 2])),
+                ("b".to_string(), nbt::Tag::Long(3).wrapped("c".to_string())),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        for tag in [tag, nbt::Tag::String("s".to_string())] {
+            let mut fw = bin::FileWriterLE::new();
+            tag.write_payload(&mut fw);
+            fw.write(&0xFFu8);
+            let bytes = fw.bytes();
+            let mut fr = bin::FileReaderLE::new(&bytes, 0);
+            assert_eq!(nbt::Tag::read(tag.tag_id(), &mut fr).unwrap(), tag);
+            assert_eq!(fr.read::<u8>().unwrap(), 0xFF);
+        }
+    }
+
+    #[test]
+    fn list_compounds() {
+        let entity = nbt::Tag::String("minecraft:pig".to_string()).wrapped("id".to_string());
+        let entities = nbt::List::from_compound_tags(vec![entity.clone(), entity]).unwrap();
+        let ids: Vec<_> = entities.compounds().unwrap().map(|e| &e["id"]).collect();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0], &nbt::Tag::String("minecraft:pig".to_string()));
+        assert!(nbt::List::Int(vec![1]).compounds().is_none());
+    }
+
+    #[test]
+    fn snbt_floats() {
+        use snbt::SnbtConfig;
+        let tag: nbt::Tag = [
+            ("f".to_string(), nbt::Tag::Float(1.0)),
+            ("d".to_string(), nbt::Tag::Double(0.5)),
+            ("a b".to_string(), nbt::Tag::ByteArray(vec![1, -2])),
+            (
+                "l".to_string(),
+                nbt::Tag::List(nbt::List::String(vec!["x\"y".to_string()])),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            tag.to_snbt(&SnbtConfig::default()),
+            r#"{"a b":[B;1B,-2B],d:0.5d,f:1.0f,l:['x"y']}"#
+        );
+        let config = SnbtConfig {
+            uppercase_suffixes: true,
+            double_suffix: false,
+            trailing_zero: false,
+            shortest: true,
+        };
+        assert_eq!(nbt::Tag::Float(1.0).to_snbt(&config), "1F");
+        for (v, snbt) in [
+            (2.0, "2.0"),
+            (1e16, "1.0e16"),
+            (-1e-7, "-1.0e-7"),
+            (0.5, "0.5"),
+        ] {
+            assert_eq!(nbt::Tag::Double(v).to_snbt(&config), snbt);
+            assert!(snbt.contains('.'));
+            assert_eq!(snbt.parse::<f64>(), Ok(v));
+        }
+        let config = SnbtConfig {
+            shortest: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            nbt::Tag::Float(0.1).to_snbt(&config),
+            "0.10000000149011612f"
+        );
+    }
+
+    #[test]
+    fn retain_entries() {
+        let compound = |entries: Vec<(&str, nbt::Tag)>| -> nbt::Tag {
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect()
+        };
+        let item = compound(vec![("id", nbt::Tag::Int(1)), ("debug", nbt::Tag::Int(2))]);
+        let items = nbt::List::from_compound_tags(vec![item.clone()]).unwrap();
+        let mut tag = compound(vec![
+            ("items", nbt::Tag::List(items.clone())),
+            ("debug", nbt::Tag::Byte(0)),
+            ("nested", item.clone()),
+        ]);
+
+        let mut shallow = tag.clone();
+        shallow.retain(|k, _| k != "debug");
+        let expected = compound(vec![("items", nbt::Tag::List(items)), ("nested", item)]);
+        assert_eq!(shallow, expected);
+
+        tag.retain_recursive(|k, _| k != "debug");
+        let item = compound(vec![("id", nbt::Tag::Int(1))]);
+        let items = nbt::List::from_compound_tags(vec![item.clone()]).unwrap();
+        let expected = compound(vec![("items", nbt::Tag::List(items)), ("nested", item)]);
+        assert_eq!(tag, expected);
+    }
+
+    #[test]
+    fn binary_patch() {
+        let compound = |entries: Vec<(&str, nbt::Tag)>| -> nbt::Tag {
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect()
+        };
+        let old = compound(vec![
+            ("a", nbt::Tag::Int(1)),
+            (
+                "b",
+                compound(vec![("c", nbt::Tag::Int(2)), ("d", nbt::Tag::Int(3))]),
+            ),
+            ("e", nbt::Tag::Byte(4)),
+        ]);
+        let new = compound(vec![
+            ("a", nbt::Tag::Int(1)),
+            (
+                "b",
+                compound(vec![("c", nbt::Tag::Long(2)), ("f", nbt::Tag::Int(5))]),
+            ),
+        ]);
+        let patch = nbt::diff_binary(&old, &new);
+        assert_eq!(nbt::apply_binary(&old, &patch).unwrap(), new);
+        assert_eq!(nbt::diff_binary(&new, &new), vec![0x00; 4]);
+
+        let patch = nbt::diff_binary(&old, &nbt::Tag::Byte(0));
+        assert_eq!(nbt::apply_binary(&old, &patch).unwrap(), nbt::Tag::Byte(0));
+        assert!(matches!(
+            nbt::apply_binary(&nbt::Tag::Byte(0), &nbt::diff_binary(&old, &new)),
+            Err(bin::BinError::MissingPatchPath(_))
+        ));
+        assert!(matches!(
+            nbt::apply_binary(&old, &[0, 0, 0, 1, 0x07, 0, 1, 0, 1, b'a']),
+            Err(bin::BinError::InvalidPatchOp(0x07))
+        ));
+        assert!(matches!(
+            nbt::apply_binary(&old, &[0, 0, 0, 1, 0x01, 0, 0]),
+            Err(bin::BinError::PatchRemovesRoot)
+        ));
+    }
+
+    #[test]
+    fn u24() {
+        let mut fw = bin::FileWriterLE::new();
+        fw.write_u24_be(0x010203);
+        fw.write_u24_be(0xFF_FFFFFF);
+        let bytes = fw.bytes();
+        assert_eq!(bytes, vec![0x01, 0x02, 0x03, 0xFF, 0xFF, 0xFF]);
+        let mut fr = bin::FileReaderLE::new(&bytes, 0);
+        assert_eq!(fr.read_u24_be().unwrap(), 0x010203);
+        assert_eq!(fr.read_u24_be().unwrap(), 0xFFFFFF);
+        assert!(fr.read_u24_be().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "sorted_compounds")]
+    fn sorted_compounds() {
+        let tag: nbt::Tag = ["b", "c", "a"]
+            .into_iter()
+            .map(|k| (k.to_string(), nbt::Tag::Byte(0)))
+            .collect();
+        let mut fw = bin::FileWriterBE::new();
+        tag.write_payload(&mut fw);
+        let bytes = fw.bytes();
+        let keys: Vec<u8> = bytes.chunks(5).filter_map(|c| c.get(3).copied()).collect();
+        assert_eq!(keys, b"abc");
+    }
+
+    #[test]
+    fn empty_and_truncated_input() {
+        let empty = Vec::new();
+        let mut fr = bin::FileReaderBE::new(&empty, 0);
+        assert!(fr.at_end());
+        assert!(matches!(
+            fr.read::<u8>(),
+            Err(bin::BinError::UnexpectedEndOfByteStream)
+        ));
+        assert!(fr.rest().is_empty());
+        assert!(nbt::read_named(&mut bin::FileReaderBE::new(&empty, 0)).is_err());
+        assert!(nbt::Tag::read(0x09, &mut bin::FileReaderBE::new(&empty, 0)).is_err());
+        assert!(matches!(
+            nbt::Tag::read(0x0A, &mut bin::FileReaderBE::new(&empty, 0)),
+            Err(bin::BinError::UnexpectedEndOfByteStream)
+        ));
+
+        let single = vec![0x0A];
+        let mut fr = bin::FileReaderBE::new(&single, 0);
+        assert!(fr.read::<i16>().is_err());
+        assert_eq!(fr.position(), 0);
+        assert_eq!(fr.read::<u8>().unwrap(), 0x0A);
+        assert!(fr.get_slice(usize::MAX).is_err());
+        assert!(fr.rest().is_empty());
+        assert!(nbt::read_named(&mut bin::FileReaderBE::new(&single, 0)).is_err());
+        assert!(nbt::Tag::read(0x09, &mut bin::FileReaderBE::new(&single, 0)).is_err());
+        assert!(nbt::Tag::read(0x0A, &mut bin::FileReaderBE::new(&single, 0)).is_err());
+    }
+
+    #[test]
+    fn memory_footprint() {
+        let tag_size = std::mem::size_of::<nbt::Tag>();
+        assert_eq!(nbt::Tag::Int(1).memory_footprint(), tag_size);
+        let array = nbt::Tag::long_array_with_capacity(16);
+        assert_eq!(array.memory_footprint(), tag_size + 16 * 8);
+        let list = nbt::Tag::List(nbt::List::String(vec!["abc".to_string()]));
+        assert!(list.memory_footprint() >= tag_size + std::mem::size_of::<String>() + 3);
+        let array_footprint = array.memory_footprint();
+        let wrapped = array.wrapped("data".to_string());
+        assert!(wrapped.memory_footprint() > tag_size + array_footprint);
+    }
+
+    #[test]
+    fn read_from() {
+        use convert::{ReadFrom, WriteTo};
+
+        #[derive(Debug, PartialEq)]
+        struct Item {
+            count: i32,
+            id: String,
+            name: Option<String>,
+        }
+
+        impl ReadFrom for Item {
+            fn read_from(tag: &nbt::Tag) -> bin::BinResult<Self> {
+                Ok(Self {
+                    count: tag.get_required_i32("count")?,
+                    id: tag.get_required("id")?,
+                    name: tag.get_optional_string("name")?.map(str::to_string),
+                })
+            }
+        }
+
+        impl WriteTo for Item {
+            fn write_to(&self) -> nbt::Tag {
+                let mut tag: nbt::Tag = [
+                    ("count".to_string(), self.count.write_to()),
+                    ("id".to_string(), self.id.write_to()),
+                ]
+                .into_iter()
+                .collect();
+                if let Some(name) = &self.name {
+                    tag.extend([("name".to_string(), name.write_to())]);
+                }
+                tag
+            }
+        }
+
+        let item = Item {
+            count: 3,
+            id: "minecraft:stone".to_string(),
+            name: None,
+        };
+        assert_eq!(Item::read_from(&item.write_to()).unwrap(), item);
+
+        let entity = item.write_to().wrapped("item".to_string());
+        assert_eq!(entity.get_required::<Item>("item").unwrap(), item);
+        fn err<T>(r: bin::BinResult<T>) -> String {
+            r.err().expect("expected an error").to_string()
+        }
+        assert!(matches!(
+            entity.get_required_i32("count"),
+            Err(bin::BinError::MissingKey(key)) if key == "count"
+        ));
+        assert_eq!(
+            err(entity.get_required_string("item")),
+            r#""item": Expected a TAG_String, found a TAG_Compound"#
+        );
+        assert!(matches!(
+            entity.get_required_string("item").unwrap_err().root_cause(),
+            bin::BinError::UnexpectedTagType {
+                expected: 0x08,
+                found: 0x0A
+            }
+        ));
+        let broken = nbt::Tag::Int(1).wrapped("count".to_string());
+        assert_eq!(
+            err(broken
+                .wrapped("item".to_string())
+                .get_required::<Item>("item")),
+            r#""item": Missing required key "id""#
+        );
+    }
+
+    #[test]
+    fn region_populated_chunks() {
+        let mut region = region::RegionFile::new();
+        assert_eq!(region.populated_chunks().count(), 0);
+        let chunk = nbt::Tag::Int(0).wrapped("DataVersion".to_string());
+        for (x, z) in [(31, 2), (4, 0), (0, 31)] {
+            region
+                .write_chunk(x, z, &chunk, compression::Compression::ZLIB)
+                .unwrap();
+        }
+        let populated: Vec<_> = region.populated_chunks().collect();
+        assert_eq!(populated, vec![(4, 0), (31, 2), (0, 31)]);
+        for (x, z) in populated {
+            assert!(region.read_chunk(x, z).unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn reused_encoder() {
+        for compression in [
+            compression::Compression::GZIP,
+            compression::Compression::ZLIB,
+            compression::Compression::Uncompressed,
+        ] {
+            let mut encoder = compression.encoder();
+            for len in [0, 1, 5000, 3] {
+                let data: Vec<u8> = (0..len).map(|i| (i % 7) as u8).collect();
+                let encoded = encoder.encode(&data).unwrap();
+                assert_eq!(compression.decode(encoded).unwrap(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn tag_try_from_bytes() {
+        let tag = nbt::Tag::Int(1).wrapped("x".to_string());
+        let mut fw = bin::FileWriterBE::new();
+        tag.write_with(nbt::NbtFlavor::JavaDisk, "root", &mut fw)
+            .unwrap();
+        let raw = fw.bytes();
+        for compression in [
+            compression::Compression::Uncompressed,
+            compression::Compression::GZIP,
+            compression::Compression::ZLIB,
+        ] {
+            let bytes = compression.encode(raw.clone()).unwrap();
+            assert_eq!(nbt::Tag::try_from(&bytes[..]).unwrap(), tag);
+            assert_eq!(
+                nbt::read_document(&bytes).unwrap(),
+                ("root".to_string(), tag.clone())
+            );
+            assert_eq!(nbt::Tag::try_from(bytes).unwrap(), tag);
+        }
+        assert!(nbt::Tag::try_from(Vec::new()).is_err());
+        assert!(nbt::Tag::try_from(&[0x0A, 0x00][..]).is_err());
+    }
+
+    #[test]
+    fn endianness() {
+        use bin::Endianness;
+        let bytes = [0x00];
+        assert_eq!(
+            bin::FileReaderBE::new(&bytes, 0).endianness(),
+            Endianness::Big
+        );
+        assert_eq!(
+            bin::FileReaderLE::new(&bytes, 0).endianness(),
+            Endianness::Little
+        );
+        assert_eq!(bin::FileWriterBE::new().endianness(), Endianness::Big);
+        assert_eq!(bin::FileWriterLE::new().endianness(), Endianness::Little);
+    }
+
+    #[test]
+    fn list_push_unique() {
+        let state = |name: &str| nbt::Tag::String(name.to_string()).wrapped("Name".to_string());
+        let mut palette = nbt::List::Empty;
+        assert_eq!(palette.push_unique(state("minecraft:air")).unwrap(), 0);
+        assert_eq!(palette.push_unique(state("minecraft:stone")).unwrap(), 1);
+        assert_eq!(palette.push_unique(state("minecraft:air")).unwrap(), 0);
+        assert_eq!(palette.len(), 2);
+        assert_eq!(
+            palette.position(|e| e == state("minecraft:stone").as_ref()),
+            Some(1)
+        );
+        assert_eq!(palette.position(|e| e == nbt::TagRef::Int(0)), None);
+        assert!(matches!(
+            palette.push_unique(nbt::Tag::Int(0)),
+            Err(bin::BinError::HeterogeneousList {
+                list_id: 0x0A,
+                element_id: 0x03
+            })
+        ));
+        assert_eq!(palette.len(), 2);
+    }
+
+    #[test]
+    #[cfg(not(feature = "sorted_compounds"))]
+    fn compound_capacity() {
+        let bytes = [0x01, 0x00, 0x01, b'a', 0x01, 0x00];
+        let read = |compound_capacity| {
+            let options = bin::ReadOptions {
+                compound_capacity,
+                ..Default::default()
+            };
+            let mut fr = bin::FileReaderBE::new(&bytes, 0).with_options(options);
+            match nbt::Tag::read(0x0A, &mut fr).unwrap() {
+                nbt::Tag::Compound(map) => map,
+                _ => unreachable!(),
+            }
+        };
+        assert!(read(bin::ReadOptions::default().compound_capacity).capacity() >= 8);
+        assert!(read(64).capacity() >= 64);
+        assert_eq!(read(0).len(), 1);
+    }
+
+    #[test]
+    fn slice_writer() {
+        let tag = nbt::Tag::Long(-2).wrapped("a".to_string());
+        for endianness in [bin::Endianness::Big, bin::Endianness::Little] {
+            let mut expected = match endianness {
+                bin::Endianness::Big => {
+                    let mut fw = bin::FileWriterBE::new();
+                    tag.write_payload(&mut fw);
+                    fw.bytes()
+                }
+                bin::Endianness::Little => {
+                    let mut fw = bin::FileWriterLE::new();
+                    tag.write_payload(&mut fw);
+                    fw.bytes()
+                }
+            };
+            let mut buf = [0xFF; 32];
+            let mut fw = bin::SliceWriter::new(&mut buf, endianness);
+            tag.write_payload(&mut fw);
+            fw.write_be(&1u16);
+            assert_eq!(fw.finish().unwrap(), expected.len() + 2);
+            expected.extend_from_slice(&[0x00, 0x01]);
+            assert_eq!(&buf[..expected.len()], &expected[..]);
+
+            let mut small = [0x00; 8];
+            let mut fw = bin::SliceWriter::new(&mut small, endianness);
+            tag.write_payload(&mut fw);
+            assert!(fw.overflowed());
+            assert!(fw.finish().is_err());
+        }
+    }
+
+    #[test]
+    fn nested_empty_lists() {
+        let list = nbt::List::List(vec![
+            nbt::List::Empty,
+            nbt::List::Int(vec![7]),
+            nbt::List::List(vec![nbt::List::Empty]),
+            nbt::List::Empty,
+        ]);
+        let mut fw = bin::FileWriterBE::new();
+        fw.write(&list);
+        let bytes = fw.bytes();
+        #[rustfmt::skip]
+        let expected = [
+            0x09, 0x00, 0x00, 0x00, 0x04,
+            0x00, 0x00, 0x00, 0x00, 0x00,
+            0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x07,
+            0x09, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert_eq!(bytes, expected);
+
+        let tag = nbt::Tag::List(list).wrapped("lists".to_string());
+        let mut fw = bin::FileWriterBE::new();
+        tag.write_payload(&mut fw);
+        let bytes = fw.bytes();
+        let mut fr = bin::FileReaderBE::new(&bytes, 0);
+        assert_eq!(nbt::Tag::read(0x0A, &mut fr).unwrap(), tag);
+        assert!(fr.at_end());
+    }
+
+    #[test]
+    fn decode_range() {
+        let zlib = compression::Compression::ZLIB;
+        let encoded = zlib.encode(b"chunk".to_vec()).unwrap();
+        let mut buf = vec![0xAA; 3];
+        buf.extend_from_slice(&encoded);
+        buf.extend_from_slice(&[0xBB; 4]);
+        let range = 3..3 + encoded.len();
+        assert_eq!(zlib.decode_range(&buf, range).unwrap(), b"chunk");
+        assert!(zlib.decode_range(&buf, 0..buf.len() + 1).is_err());
+        assert_eq!(
+            compression::Compression::Uncompressed
+                .decode_range(&buf, 0..3)
+                .unwrap(),
+            [0xAA; 3]
+        );
+    }
+
+    #[test]
+    fn flatten() {
+        let item: nbt::Tag = [
+            (
+                "id".to_string(),
+                nbt::Tag::String("minecraft:apple".to_string()),
+            ),
+            ("Count".to_string(), nbt::Tag::Byte(2)),
+        ]
+        .into_iter()
+        .collect();
+        let mut items = nbt::List::Empty;
+        items.push_unique(item).unwrap();
+        let tag: nbt::Tag = [
+            ("Inventory".to_string(), nbt::Tag::List(items)),
+            ("Pos.x".to_string(), nbt::Tag::Double(1.5)),
+            (
+                "Empty".to_string(),
+                nbt::Tag::Compound(nbt::Compound::new()),
+            ),
+            (
+                "Nested".to_string(),
+                nbt::Tag::List(nbt::List::List(vec![nbt::List::Int(vec![4])])),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let mut leaves = tag.flatten();
+        leaves.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            leaves,
+            vec![
+                ("\"Pos.x\"".to_string(), nbt::Tag::Double(1.5)),
+                ("Inventory[0].Count".to_string(), nbt::Tag::Byte(2)),
+                (
+                    "Inventory[0].id".to_string(),
+                    nbt::Tag::String("minecraft:apple".to_string())
+                ),
+                ("Nested[0][0]".to_string(), nbt::Tag::Int(4)),
+            ]
+        );
+        assert_eq!(
+            nbt::Tag::Int(1).flatten(),
+            vec![(String::new(), nbt::Tag::Int(1))]
+        );
+    }
+
+    #[test]
+    fn decode_block_states() {
+        let palette = |len: usize| {
+            nbt::List::String((0..len).map(|i| format!("minecraft:block_{}", i)).collect())
+        };
+        let states: Vec<u32> = (0..4096).map(|i| (i * 7 % 17) as u32).collect();
+
+        // 17 entries need 5 bits: 12 per long when padded, spanning longs otherwise
+        let mut padded = vec![0i64; 4096usize.div_ceil(12)];
+        let mut packed = vec![0i64; 4096 * 5 / 64];
+        for (i, &v) in states.iter().enumerate() {
+            padded[i / 12] |= (v as i64) << (i % 12 * 5);
+            let bit = i * 5;
+            packed[bit / 64] |= ((v as u64) << (bit % 64)) as i64;
+            if bit % 64 + 5 > 64 {
+                packed[bit / 64 + 1] |= (v as u64 >> (64 - bit % 64)) as i64;
+            }
+        }
+
+        let modern: nbt::Tag = [
+            ("palette".to_string(), nbt::Tag::List(palette(17))),
+            ("data".to_string(), nbt::Tag::LongArray(padded.clone())),
+        ]
+        .into_iter()
+        .collect();
+        let modern = modern.wrapped("block_states".to_string());
+        assert_eq!(nbt::decode_block_states(&modern, 3955).unwrap(), states);
+
+        let legacy: nbt::Tag = [
+            ("Palette".to_string(), nbt::Tag::List(palette(17))),
+            ("BlockStates".to_string(), nbt::Tag::LongArray(packed)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(nbt::decode_block_states(&legacy, 2230).unwrap(), states);
+        assert!(matches!(
+            nbt::decode_block_states(&legacy, 2586),
+            Err(bin::BinError::BlockStatesLength {
+                len: 320,
+                bits: 5,
+                expected: 342
+            })
+        ));
+        assert!(matches!(
+            nbt::decode_block_states(&nbt::Tag::Compound(nbt::Compound::new()), 3955),
+            Err(bin::BinError::MissingBlockPalette)
+        ));
+
+        let uniform = nbt::Tag::List(palette(1))
+            .wrapped("palette".to_string())
+            .wrapped("block_states".to_string());
+        assert_eq!(
+            nbt::decode_block_states(&uniform, 3955).unwrap(),
+            vec![0; 4096]
+        );
+        let out_of_palette: nbt::Tag = [
+            ("palette".to_string(), nbt::Tag::List(palette(17))),
+            ("data".to_string(), nbt::Tag::LongArray(vec![-1; 342])),
+        ]
+        .into_iter()
+        .collect();
+        assert!(matches!(
+            nbt::decode_block_states(&out_of_palette.wrapped("block_states".to_string()), 3955),
+            Err(bin::BinError::BlockStateOutOfPalette {
+                block: 0,
+                entry: 31,
+                palette_len: 17
+            })
+        ));
+    }
+
+    #[test]
+    fn push_arrays() {
+        let mut list = nbt::List::Empty;
+        list.push_int_array(vec![1, 2]).unwrap();
+        list.push_int_array(vec![]).unwrap();
+        assert_eq!(list, nbt::List::IntArray(vec![vec![1, 2], vec![]]));
+        assert!(list.push_long_array(vec![3]).is_err());
+        assert!(list.push_byte_array(vec![3]).is_err());
+
+        let mut list = nbt::List::Empty;
+        list.push_long_array(vec![i64::MIN]).unwrap();
+        assert_eq!(list.get(0), Some(nbt::TagRef::LongArray(&[i64::MIN])));
+        let mut list = nbt::List::Empty;
+        list.push_byte_array(vec![-1]).unwrap();
+        assert_eq!(list.element_id(), 0x07);
+    }
+
+    #[test]
+    fn read_all_documents() {
+        let mut fw = bin::FileWriterBE::new();
+        for i in 0..3 {
+            let tag = nbt::Tag::Int(i).wrapped("i".to_string());
+            tag.write_with(nbt::NbtFlavor::JavaDisk, &format!("doc{}", i), &mut fw)
+                .unwrap();
+        }
+        let mut bytes = fw.bytes();
+        let documents = nbt::Tag::read_all(&mut bin::FileReaderBE::new(&bytes, 0)).unwrap();
+        assert_eq!(documents.len(), 3);
+        assert_eq!(
+            documents[2],
+            (
+                "doc2".to_string(),
+                nbt::Tag::Int(2).wrapped("i".to_string())
+            )
+        );
+        assert!(nbt::Tag::read_all(&mut bin::FileReaderBE::new(&[], 0))
+            .unwrap()
+            .is_empty());
+
+        let len = bytes.len();
+        bytes.extend_from_slice(&[0x0D, 0x00, 0x00]);
+        match nbt::Tag::read_all(&mut bin::FileReaderBE::new(&bytes, 0)) {
+            Err(bin::BinError::Context { context, source }) => {
+                assert_eq!(context, format!("Document 3 at byte {}", len));
+                assert!(matches!(
+                    *source,
+                    bin::BinError::InvalidTagId { tag_id: 0x0D, .. }
+                ));
+            }
+            _ => panic!("expected an error with context"),
+        }
+    }
+
+    #[test]
+    fn hashable_tag() {
+        let state = |name: &str, axis: &str| {
+            let properties = nbt::Tag::String(axis.to_string()).wrapped("axis".to_string());
+            let tag: nbt::Tag = [
+                ("Name".to_string(), nbt::Tag::String(name.to_string())),
+                ("Properties".to_string(), properties),
+            ]
+            .into_iter()
+            .collect();
+            nbt::HashableTag::new(tag).unwrap()
+        };
+        let mut palette = std::collections::HashMap::new();
+        palette.insert(state("minecraft:oak_log", "y"), 0);
+        palette.insert(state("minecraft:oak_log", "x"), 1);
+        assert_eq!(palette[&state("minecraft:oak_log", "y")], 0);
+        assert_eq!(palette.len(), 2);
+
+        assert!(nbt::HashableTag::new(nbt::Tag::Float(1.0)).is_none());
+        let nested = nbt::Tag::List(nbt::List::Double(vec![0.0])).wrapped("Pos".to_string());
+        assert!(nbt::HashableTag::new(nested).is_none());
+        assert!(nbt::HashableTag::new(nbt::Tag::List(nbt::List::Empty)).is_some());
+    }
+
+    #[test]
+    fn read_counted() {
+        let mut fw = bin::FileWriterBE::new();
+        fw.write(&0xCAFEu16);
+        nbt::Tag::Short(5)
+            .wrapped("s".to_string())
+            .write_payload(&mut fw);
+        fw.write(&0xBEEFu16);
+        let bytes = fw.bytes();
+        let mut fr = bin::FileReaderBE::new(&bytes, 2);
+        let (tag, len) = nbt::Tag::read_counted(0x0A, &mut fr).unwrap();
+        assert_eq!(tag, nbt::Tag::Short(5).wrapped("s".to_string()));
+        assert_eq!(len, 7);
+        assert_eq!(fr.read::<u16>().unwrap(), 0xBEEF);
+    }
+
+    #[test]
+    fn unknown_tag_ids() {
+        #[rustfmt::skip]
+        let bytes = [
+            0x0D, 0x00, 0x01, b'm', 0xAA, 0xBB,
+            0x09, 0x00, 0x01, b'l', 0x0D, 0x00, 0x00, 0x00, 0x02, 0xAA, 0xBB, 0xCC, 0xDD,
+            0x01, 0x00, 0x01, b'b', 0x05,
+            0x00,
+        ];
+        match nbt::Tag::read(0x0A, &mut bin::FileReaderBE::new(&bytes, 0)) {
+            Err(e) => {
+                assert!(matches!(
+                    e,
+                    bin::BinError::InvalidTagId {
+                        tag_id: 0x0D,
+                        pos: 4
+                    }
+                ));
+                assert_eq!(e.to_string(), "Invalid Tag ID: 13 at byte 4");
+            }
+            _ => panic!("expected an error"),
+        }
+
+        let options = bin::ReadOptions {
+            unknown_tag_size: Some(|tag_id| (tag_id == 0x0D).then_some(2)),
+            ..Default::default()
+        };
+        let mut fr = bin::FileReaderBE::new(&bytes, 0).with_options(options);
+        let tag = nbt::Tag::read(0x0A, &mut fr).unwrap();
+        let expected: nbt::Tag = [
+            ("l".to_string(), nbt::Tag::List(nbt::List::Empty)),
+            ("b".to_string(), nbt::Tag::Byte(5)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(tag, expected);
+        assert!(fr.at_end());
+    }
+
+    #[test]
+    fn write_named_tag() {
+        let tag = nbt::Tag::Int(3).wrapped("x".to_string());
+        let mut fw = bin::FileWriterLE::new();
+        fw.write_named_tag("root", &tag);
+        let bytes = fw.bytes();
+        assert_eq!(&bytes[..7], [0x0A, 0x04, 0x00, b'r', b'o', b'o', b't']);
+        let mut fr = bin::FileReaderLE::new(&bytes, 0);
+        assert_eq!(nbt::read_named(&mut fr).unwrap(), ("root".to_string(), tag));
+        assert!(fr.at_end());
+    }
+
+    #[test]
+    fn detect_compression() {
+        use compression::Compression;
+        let detect = Compression::detect;
+        assert_eq!(detect(&[0x1F, 0x8B, 0x08]), Some(Compression::GZIP));
+        assert_eq!(detect(&[0x78, 0x9C]), Some(Compression::ZLIB));
+        assert_eq!(detect(&[0x0A, 0x00, 0x00]), Some(Compression::Uncompressed));
+        assert_eq!(detect(&[0x0C]), Some(Compression::Uncompressed));
+        assert_eq!(detect(&[0x00]), None);
+        assert_eq!(detect(&[0x1F, 0x00]), None);
+        assert_eq!(detect(&[]), None);
+        assert!(matches!(
+            nbt::Tag::try_from(&[0xFF, 0x00][..]),
+            Err(bin::BinError::UnrecognizedCompression(0xFF))
+        ));
+    }
+
+    #[test]
+    fn widen_lists() {
+        let bytes = nbt::List::Byte(vec![-1, 2]);
+        assert_eq!(bytes.widen_to(0x03).unwrap(), nbt::List::Int(vec![-1, 2]));
+        assert_eq!(
+            bytes.widen_to(0x06).unwrap(),
+            nbt::List::Double(vec![-1.0, 2.0])
+        );
+        assert_eq!(bytes.widen_to(0x01).unwrap(), bytes);
+        let ints = nbt::List::Int(vec![i32::MAX]);
+        assert_eq!(
+            ints.widen_to(0x04).unwrap(),
+            nbt::List::Long(vec![i32::MAX as i64])
+        );
+        assert!(matches!(
+            ints.widen_to(0x05),
+            Err(bin::BinError::IncompatibleListWiden {
+                from: 0x03,
+                to: 0x05
+            })
+        ));
+        assert!(ints.widen_to(0x02).is_err());
+        assert_eq!(
+            nbt::List::Float(vec![0.5]).widen_to(0x06).unwrap(),
+            nbt::List::Double(vec![0.5])
+        );
+        assert!(nbt::List::String(vec![]).widen_to(0x03).is_err());
+        assert_eq!(nbt::List::Empty.widen_to(0x04).unwrap(), nbt::List::Empty);
+    }
+
+    #[test]
+    fn validate() {
+        let tag = nbt::Tag::String("ok".to_string()).wrapped("a".to_string());
+        assert_eq!(tag.validate(), Ok(()));
+
+        let long = "\u{10000}".repeat(10923);
+        let mut strings = nbt::List::Empty;
+        strings.push_unique(nbt::Tag::String(long.clone())).unwrap();
+        let tag: nbt::Tag = [
+            ("name".to_string(), nbt::Tag::String("\0".repeat(40000))),
+            ("lines".to_string(), nbt::Tag::List(strings)),
+            (long, nbt::Tag::Byte(0)),
+        ]
+        .into_iter()
+        .collect();
+        let mut errors = tag.validate().unwrap_err();
+        errors.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[1].path, "lines[0]");
+        assert_eq!(
+            errors[2].to_string(),
+            "name: String is 80000 bytes long, at most 65535 fit"
+        );
+        assert_eq!(
+            errors[0].message,
+            "Key is 65538 bytes long, at most 65535 fit"
+        );
+    }
+
+    #[test]
+    fn growable_reader() {
+        let mut fw = bin::FileWriterBE::new();
+        fw.write_named_tag("", &nbt::Tag::Long(1).wrapped("l".to_string()));
+        fw.write(&7u16);
+        let bytes = fw.bytes();
+
+        let mut fr = bin::GrowableReader::new(bin::Endianness::Big);
+        let mut chunks = bytes.chunks(4);
+        let parsed = loop {
+            fr.feed(chunks.next().unwrap());
+            match fr.try_read(nbt::read_named).unwrap() {
+                Some(parsed) => break parsed,
+                None => assert_eq!(fr.position(), 0),
+            }
+        };
+        assert_eq!(parsed.1, nbt::Tag::Long(1).wrapped("l".to_string()));
+        fr.compact();
+        assert_eq!(fr.try_read(|fr| fr.read::<u16>()).unwrap(), None);
+        fr.feed(chunks.next().unwrap());
+        assert_eq!(fr.read_le::<u16>().unwrap(), 0x0700);
+        assert!(fr.at_end());
+
+        fr.set_position(10);
+        fr.compact();
+        assert_eq!(fr.position(), 0);
+        assert!(fr.at_end());
+    }
+
+    #[test]
+    fn bedrock_long_arrays() {
+        let flavor = nbt::NbtFlavor::BedrockDisk;
+        assert!(flavor.supports_tag_id(0x0B));
+        assert!(!flavor.supports_tag_id(0x0C));
+        assert!(nbt::NbtFlavor::JavaDisk.supports_tag_id(0x0C));
+
+        let mut longs = nbt::List::Empty;
+        longs.push_long_array(vec![1]).unwrap();
+        let nested = nbt::Tag::List(nbt::List::List(vec![longs])).wrapped("a".to_string());
+        for tag in [
+            nbt::Tag::LongArray(vec![1]).wrapped("a".to_string()),
+            nested,
+        ] {
+            let mut fw = bin::FileWriterLE::new();
+            assert!(matches!(
+                tag.write_with(flavor, "", &mut fw),
+                Err(bin::BinError::UnsupportedTag { tag_id: 0x0C, .. })
+            ));
+
+            let mut fw = bin::FileWriterLE::new();
+            tag.write_with(nbt::NbtFlavor::JavaDisk, "", &mut fw)
+                .unwrap();
+            let bytes = fw.bytes();
+            let mut fr = bin::FileReaderLE::new(&bytes, 0);
+            assert!(nbt::Tag::read_with(flavor, &mut fr).is_err());
+        }
+
+        let ints = nbt::Tag::IntArray(vec![1]).wrapped("a".to_string());
+        let mut fw = bin::FileWriterLE::new();
+        ints.write_with(flavor, "", &mut fw).unwrap();
+        let bytes = fw.bytes();
+        let mut fr = bin::FileReaderLE::new(&bytes, 0);
+        assert_eq!(nbt::Tag::read_with(flavor, &mut fr).unwrap().1, ints);
+    }
+
+    #[test]
+    fn extend_borrowed_bytes() {
+        let mut fw = bin::FileWriterBE::new();
+        fw.extend(&[0x01, 0x02]);
+        fw.append(&mut vec![0x03]);
+        fw.write_u24_be(0x040506);
+        assert_eq!(fw.bytes(), vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06]);
+
+        let mut buf = [0x00; 3];
+        let mut fw = bin::SliceWriter::new(&mut buf, bin::Endianness::Little);
+        fw.extend(&[0x0A, 0x0B]);
+        fw.extend(&[0x0C, 0x0D]);
+        assert!(fw.finish().is_err());
+        assert_eq!(buf, [0x0A, 0x0B, 0x00]);
+    }
+
+    #[test]
+    fn float_bit_patterns() {
+        fn roundtrip(tag: &nbt::Tag) -> nbt::Tag {
+            let mut fw = bin::FileWriterBE::new();
+            tag.write_payload(&mut fw);
+            let bytes = fw.bytes();
+            nbt::Tag::read(tag.tag_id(), &mut bin::FileReaderBE::new(&bytes, 0)).unwrap()
+        }
+        for v in [
+            f32::NAN,
+            -f32::NAN,
+            f32::from_bits(0x7F80_0001),
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            -0.0,
+            f32::MIN_POSITIVE / 2.0,
+        ] {
+            match roundtrip(&nbt::Tag::Float(v)) {
+                nbt::Tag::Float(r) => assert_eq!(r.to_bits(), v.to_bits()),
+                tag => panic!("{:?}", tag),
+            }
+            let mut fw = bin::FileWriterLE::new();
+            fw.write(&v);
+            let bytes = fw.bytes();
+            let r: f32 = bin::FileReaderLE::new(&bytes, 0).read().unwrap();
+            assert_eq!(r.to_bits(), v.to_bits());
+        }
+        for v in [
+            f64::NAN,
+            -f64::NAN,
+            f64::from_bits(0x7FF0_0000_0000_0001),
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            -0.0,
+            f64::MIN_POSITIVE / 2.0,
+        ] {
+            match roundtrip(&nbt::Tag::Double(v)) {
+                nbt::Tag::Double(r) => assert_eq!(r.to_bits(), v.to_bits()),
+                tag => panic!("{:?}", tag),
+            }
+            let mut fw = bin::FileWriterLE::new();
+            fw.write(&v);
+            let bytes = fw.bytes();
+            let r: f64 = bin::FileReaderLE::new(&bytes, 0).read().unwrap();
+            assert_eq!(r.to_bits(), v.to_bits());
+        }
+    }
+
+    #[test]
+    fn world_region_dir() {
+        let dir = std::env::temp_dir().join("mm_io_world_region_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let chunk = nbt::Tag::Int(5).wrapped("x".to_string());
+        let mut region = region::RegionFile::new();
+        region
+            .write_chunk(3, 4, &chunk, compression::Compression::ZLIB)
+            .unwrap();
+        region
+            .write_chunk(0, 1, &chunk, compression::Compression::GZIP)
+            .unwrap();
+        std::fs::write(dir.join("r.-1.2.mca"), region.bytes()).unwrap();
+        std::fs::write(dir.join("r.0.0.mca"), [0x00; 16]).unwrap();
+        std::fs::write(dir.join("level.dat"), [0x00]).unwrap();
+
+        let mut chunks: Vec<_> = region::read_world_region_dir(&dir).unwrap().collect();
+        chunks.sort_by_key(|(rx, rz, x, z, _)| (*rx, *rz, *x, *z));
+        let (failed, read): (Vec<_>, Vec<_>) = chunks.into_iter().partition(|c| c.4.is_err());
+        let read: Vec<_> = read
+            .into_iter()
+            .map(|(rx, rz, x, z, read)| {
+                assert_eq!(read.unwrap(), chunk);
+                (rx, rz, x, z)
+            })
+            .collect();
+        assert_eq!(read, [(-1, 2, -32, 65), (-1, 2, -29, 68)]);
+        assert_eq!(failed.len(), 1);
+        let (rx, rz, x, z, error) = &failed[0];
+        assert_eq!((*rx, *rz, *x, *z), (0, 0, 0, 0));
+        let error = error.as_ref().unwrap_err().to_string();
+        assert!(error.starts_with("Opening "), "{}", error);
+        assert!(
+            error.contains("r.0.0.mca") && !error.contains("Chunk"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn mutf8_strings() {
+        for (s, encoded) in [
+            ("a\0b", vec![b'a', 0xC0, 0x80, b'b']),
+            ("\u{1F600}", vec![0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]),
+            ("é\u{FFFF}", vec![0xC3, 0xA9, 0xEF, 0xBF, 0xBF]),
+        ] {
+            let mut fw = bin::FileWriterBE::new();
+            fw.write(&s.to_string());
+            let bytes = fw.bytes();
+            assert_eq!(bytes[..2], (encoded.len() as u16).to_be_bytes());
+            assert_eq!(bytes[2..], encoded[..]);
+            let mut fr = bin::FileReaderBE::new(&bytes, 0);
+            assert_eq!(fr.read::<String>().unwrap(), s);
+            assert!(fr.at_end());
+        }
+
+        let invalid = &[0x00, 0x02, b'a', 0xFF];
+        let lenient = bin::FileReaderBE::new(invalid, 0).read::<String>();
+        assert_eq!(lenient.unwrap(), "a\u{FFFD}");
+        let options = bin::ReadOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let strict = bin::FileReaderBE::new(invalid, 0)
+            .with_options(options)
+            .read::<String>();
+        assert!(matches!(strict, Err(bin::BinError::InvalidMutf8(_))));
+    }
+
+    #[test]
+    fn list_append() {
+        let mut list = nbt::List::Empty;
+        list.append(nbt::List::Empty).unwrap();
+        assert_eq!(list, nbt::List::Empty);
+        list.append(nbt::List::Int(vec![1, 2])).unwrap();
+        list.append(nbt::List::Empty).unwrap();
+        list.append(nbt::List::Int(vec![3])).unwrap();
+        assert_eq!(list, nbt::List::Int(vec![1, 2, 3]));
+
+        let err = list.append(nbt::List::Short(vec![4])).unwrap_err();
+        assert!(matches!(
+            err,
+            bin::BinError::HeterogeneousList {
+                list_id: 0x03,
+                element_id: 0x02
+            }
+        ));
+        assert_eq!(list, nbt::List::Int(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn region_raw_chunks() {
+        let chunk = nbt::Tag::Int(5).wrapped("x".to_string());
+        let mut source = region::RegionFile::new();
+        source
+            .write_chunk(2, 7, &chunk, compression::Compression::GZIP)
+            .unwrap();
+        assert!(source.read_chunk_raw(0, 0).unwrap().is_none());
+        let (compression, data) = source.read_chunk_raw(2, 7).unwrap().unwrap();
+        assert_eq!(compression, compression::Compression::GZIP);
+        assert_eq!(&data[..2], &[0x1F, 0x8B]);
+
+        let mut target = region::RegionFile::new();
+        target
+            .write_chunk_raw(31, 0, compression, data.clone())
+            .unwrap();
+        assert_eq!(target.read_chunk(31, 0).unwrap(), Some(chunk));
+        let (compression, copied) = target.read_chunk_raw(31, 0).unwrap().unwrap();
+        assert_eq!(compression, compression::Compression::GZIP);
+        assert_eq!(copied, data);
+    }
+
+    #[test]
+    fn bedrock_network_varints() {
+        use nbt::NbtFlavor;
+        let tag = nbt::Tag::List(nbt::List::Int(vec![1, -300])).wrapped("l".to_string());
+        let bytes = vec![
+            0x0A, 0x00, 0x09, 0x01, b'l', 0x03, 0x04, 0x02, 0xD7, 0x04, 0x00,
+        ];
+        let mut fw = bin::FileWriterBE::new();
+        tag.write_with(NbtFlavor::BedrockNetwork, "", &mut fw)
+            .unwrap();
+        assert_eq!(fw.bytes(), bytes);
+        let mut fr = bin::FileReaderBE::new(&bytes, 0);
+        let root = nbt::Tag::read_with(NbtFlavor::BedrockNetwork, &mut fr).unwrap();
+        assert_eq!(root, (String::new(), tag));
+        assert!(fr.at_end());
+
+        let mut map = nbt::Compound::new();
+        map.insert("long".to_string(), nbt::Tag::Long(i64::MIN));
+        map.insert("int".to_string(), nbt::Tag::Int(i32::MAX));
+        map.insert("ints".to_string(), nbt::Tag::IntArray(vec![0, -1, 64]));
+        map.insert("name".to_string(), nbt::Tag::String("é".repeat(100)));
+        let tag = nbt::Tag::Compound(map);
+        let mut fw = bin::FileWriterLE::new();
+        tag.write_with(NbtFlavor::BedrockNetwork, "root", &mut fw)
+            .unwrap();
+        let bytes = fw.bytes();
+        let mut fr = bin::FileReaderLE::new(&bytes, 0);
+        let root = nbt::Tag::read_with(NbtFlavor::BedrockNetwork, &mut fr).unwrap();
+        assert_eq!(root, ("root".to_string(), tag));
+
+        let options = bin::ReadOptions {
+            ints: bin::IntEncoding::VarInt,
+            ..Default::default()
+        };
+        let mut fr =
+            bin::FileReaderLE::new(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F], 0).with_options(options);
+        assert!(matches!(
+            fr.read::<i32>(),
+            Err(bin::BinError::VarIntOverflow { ty: "i32" })
+        ));
+        let mut max = vec![0xFF; 9];
+        max.push(0x01);
+        assert_eq!(
+            bin::FileReaderLE::new(&max, 0).read_varint().unwrap(),
+            u64::MAX
+        );
+        *max.last_mut().unwrap() = 0x02;
+        assert!(matches!(
+            bin::FileReaderLE::new(&max, 0).read_varint(),
+            Err(bin::BinError::VarIntOverflow { ty: "u64" })
+        ));
+    }
+
+    #[test]
+    fn container_accessors() {
+        let mut tag = nbt::Tag::List(nbt::List::Empty).wrapped("items".to_string());
+        let items = tag
+            .as_compound_mut()
+            .unwrap()
+            .get_mut("items")
+            .and_then(nbt::Tag::as_list_mut)
+            .unwrap();
+        items.push_unique(nbt::Tag::Int(1)).unwrap();
+        tag.as_compound_mut()
+            .unwrap()
+            .insert("count".to_string(), nbt::Tag::Byte(1));
+
+        let map = tag.as_compound().unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["items"].as_list(), Some(&nbt::List::Int(vec![1])));
+        assert!(map["count"].as_list().is_none());
+        assert!(nbt::Tag::Int(0).as_compound().is_none());
+        assert!(nbt::Tag::Int(0).as_list_mut().is_none());
+    }
+
+    #[test]
+    fn compound_list_roundtrip() {
+        let mut nested = nbt::Compound::new();
+        nested.insert("id".to_string(), nbt::Tag::String("stone".to_string()));
+        nested.insert(
+            "tag".to_string(),
+            nbt::Tag::Int(3).wrapped("depth".to_string()),
+        );
+        let mut entry = nbt::Compound::new();
+        entry.insert("item".to_string(), nbt::Tag::Compound(nested));
+        entry.insert("slot".to_string(), nbt::Tag::Byte(2));
+        let mut list = nbt::List::Empty;
+        list.push_compound(entry).unwrap();
+        list.push_compound(nbt::Compound::new()).unwrap();
+        list.push_compound(nbt::Compound::from([(
+            "x".to_string(),
+            nbt::Tag::List(nbt::List::Empty),
+        )]))
+        .unwrap();
+        let tag = nbt::Tag::List(list).wrapped("entries".to_string());
+
+        let mut fw = bin::FileWriterBE::new();
+        tag.write_payload(&mut fw);
+        fw.write::<u8>(&0x7F);
+        let bytes = fw.bytes();
+        let mut fr = bin::FileReaderBE::new(&bytes, 0);
+        assert_eq!(nbt::Tag::read(0x0A, &mut fr).unwrap(), tag);
+        assert_eq!(fr.read::<u8>().unwrap(), 0x7F);
+        assert!(fr.at_end());
+    }
+
+    #[test]
+    fn clone_into_reuses_buffers() {
+        let source: nbt::Tag = [
+            ("bytes".to_string(), nbt::Tag::ByteArray(vec![1; 64])),
+            ("name".to_string(), nbt::Tag::String("b".repeat(32))),
+            (
+                "list".to_string(),
+                nbt::Tag::List(nbt::List::Compound(vec![nbt::Compound::new(); 3])),
+            ),
+        ]
+        .into_iter()
+        .collect();
+        let mut dst: nbt::Tag = [
+            ("bytes".to_string(), nbt::Tag::ByteArray(vec![0; 128])),
+            ("name".to_string(), nbt::Tag::Int(1)),
+            ("gone".to_string(), nbt::Tag::Byte(1)),
+        ]
+        .into_iter()
+        .collect();
+        let bytes_ptr = match &dst.as_compound().unwrap()["bytes"] {
+            nbt::Tag::ByteArray(v) => v.as_ptr(),
+            _ => unreachable!(),
+        };
+        source.clone_into(&mut dst);
+        assert_eq!(dst, source);
+        match &dst.as_compound().unwrap()["bytes"] {
+            nbt::Tag::ByteArray(v) => assert_eq!(v.as_ptr(), bytes_ptr),
+            _ => unreachable!(),
+        }
+
+        let mut list = nbt::List::Int(vec![0; 16]);
+        nbt::List::String(vec!["a".to_string()]).clone_into(&mut list);
+        assert_eq!(list, nbt::List::String(vec!["a".to_string()]));
+    }
+
+    #[test]
+    fn endian_free_functions() {
+        let tag = nbt::Tag::Short(0x0102).wrapped("s".to_string());
+        let be = nbt::write_be("r", &tag);
+        assert_eq!(
+            be,
+            vec![0x0A, 0x00, 0x01, b'r', 0x02, 0x00, 0x01, b's', 0x01, 0x02, 0x00]
+        );
+        assert_eq!(nbt::read_be(&be).unwrap(), ("r".to_string(), tag.clone()));
+        let le = nbt::write_le("r", &tag);
+        assert_eq!(
+            le,
+            vec![0x0A, 0x01, 0x00, b'r', 0x02, 0x01, 0x00, b's', 0x02, 0x01, 0x00]
+        );
+        assert_eq!(nbt::read_le(&le).unwrap(), ("r".to_string(), tag));
+        assert!(nbt::read_be(&le).is_err());
+    }
+
+    #[test]
+    fn bedrock_level_dat() {
+        let tag = nbt::Tag::Int(7).wrapped("x".to_string());
+        let bytes = nbt::write_bedrock_level(10, &tag).unwrap();
+        assert_eq!(
+            &bytes[..8],
+            &[0x0A, 0x00, 0x00, 0x00, 0x0C, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(nbt::read_bedrock_level(&bytes).unwrap(), (10, tag));
+
+        let mut truncated = bytes.clone();
+        truncated.pop();
+        assert!(matches!(
+            nbt::read_bedrock_level(&truncated),
+            Err(bin::BinError::LevelLengthMismatch { .. })
+        ));
+        assert!(matches!(
+            nbt::read_bedrock_level(&bytes[..6]),
+            Err(bin::BinError::UnexpectedEndOfByteStream)
+        ));
+    }
+
+    #[test]
+    fn expect_bytes() {
+        let mut fr = bin::FileReaderBE::new(&[0xCA, 0x00, 0x03, 0xFE], 0);
+        fr.expect_u8(0xCA).unwrap();
+        fr.expect_tag_id(0x00).unwrap();
+        let err = fr.expect_tag_id(0x00).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Tag id at byte 2: Expected a TAG_End, found a TAG_Int"
+        );
+        let err = fr.expect_u8(0xFF).unwrap_err();
+        assert_eq!(err.to_string(), "Expected byte 0xFF, found 0xFE at byte 3");
+        assert!(matches!(
+            fr.expect_u8(0x00),
+            Err(bin::BinError::UnexpectedEndOfByteStream)
+        ));
+    }
+
+    #[test]
+    fn typed_empty_lists() {
+        let bytes = &[0x03, 0x00, 0x00, 0x00, 0x00];
+        let tag = nbt::Tag::read(0x09, &mut bin::FileReaderBE::new(bytes, 0)).unwrap();
+        assert_eq!(tag, nbt::Tag::List(nbt::List::Empty));
+        let options = bin::ReadOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let mut fr = bin::FileReaderBE::new(bytes, 0).with_options(options);
+        assert!(matches!(
+            nbt::Tag::read(0x09, &mut fr),
+            Err(bin::BinError::TypedEmptyList(0x03))
+        ));
+        let mut fr = bin::FileReaderBE::new(&[0x00; 5], 0).with_options(options);
+        assert!(nbt::Tag::read(0x09, &mut fr).is_ok());
+
+        let mut fw = bin::FileWriterBE::new();
+        fw.write(&nbt::List::Int(vec![]));
+        assert_eq!(fw.bytes(), vec![0x00; 5]);
+    }
+
+    #[test]
+    fn count_nodes() {
+        let tag: nbt::Tag = [
+            ("a".to_string(), nbt::Tag::List(nbt::List::Int(vec![1, 2]))),
+            ("b".to_string(), nbt::Tag::IntArray(vec![1, 2, 3])),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(tag.count_nodes(), 5);
+        assert_eq!(nbt::Tag::Int(1).count_nodes(), 1);
+        assert_eq!(nbt::Tag::List(nbt::List::Empty).count_nodes(), 1);
+        let nested = nbt::Tag::List(nbt::List::List(vec![
+            nbt::List::Empty,
+            nbt::List::Compound(vec![tag.as_compound().unwrap().clone()]),
+        ]));
+        assert_eq!(nested.count_nodes(), 1 + 1 + 1 + 5);
+    }
+
+    #[test]
+    fn streamed_compression() {
+        let mut map = nbt::Compound::new();
+        for i in 0..64 {
+            let values = (0..1024).map(|v| v * i).collect();
+            map.insert(format!("ints{}", i), nbt::Tag::IntArray(values));
+        }
+        let tag = nbt::Tag::Compound(map);
+        for compression in [
+            compression::Compression::Uncompressed,
+            compression::Compression::GZIP,
+            compression::Compression::ZLIB,
+        ] {
+            let buffered = compression.encode(nbt::write_be("root", &tag)).unwrap();
+            let streamed = nbt::write_compressed("root", &tag, &compression, Vec::new()).unwrap();
+            assert_eq!(streamed, buffered, "{}", compression.as_str());
+        }
+
+        let mut fw = bin::StreamWriter::new(Vec::new(), bin::Endianness::Little);
+        fw.write(&1u16);
+        fw.pad_to(4);
+        fw.write_be(&2u16);
+        assert_eq!(fw.position(), 6);
+        assert_eq!(
+            fw.finish().unwrap(),
+            vec![0x01, 0x00, 0x00, 0x00, 0x00, 0x02]
+        );
+    }
+
+    #[test]
+    fn tag_id_constants() {
+        assert_eq!(nbt::TAG_NAMES.len(), nbt::TAG_LONG_ARRAY as usize + 1);
+        assert_eq!(nbt::TAG_NAMES[nbt::TAG_COMPOUND as usize], "TAG_Compound");
+        assert_eq!(nbt::Tag::LongArray(vec![]).tag_id(), nbt::TAG_LONG_ARRAY);
+
+        let list = nbt::List::LongArray(vec![vec![1, -1], vec![]]);
+        let mut fw = bin::FileWriterBE::new();
+        fw.write(&list);
+        let bytes = fw.bytes();
+        assert_eq!(bytes[0], nbt::TAG_LONG_ARRAY);
+        let read = nbt::Tag::read(nbt::TAG_LIST, &mut bin::FileReaderBE::new(&bytes, 0)).unwrap();
+        assert_eq!(read, nbt::Tag::List(list));
+    }
+
+    #[test]
+    fn compound_terminator() {
+        let closed = &[0x01, 0x00, 0x01, b'a', 0x05, 0x00, 0xFF];
+        let mut fr = bin::FileReaderBE::new(closed, 0);
+        let tag = nbt::Tag::read(nbt::TAG_COMPOUND, &mut fr).unwrap();
+        assert_eq!(tag, nbt::Tag::Byte(5).wrapped("a".to_string()));
+        assert_eq!(fr.position(), 6);
+
+        let mut fr = bin::FileReaderBE::new(&closed[..5], 0);
+        assert!(matches!(
+            nbt::Tag::read(nbt::TAG_COMPOUND, &mut fr),
+            Err(bin::BinError::UnexpectedEndOfByteStream)
+        ));
+
+        let mut fw = bin::FileWriterBE::new();
+        fw.write(&tag);
+        assert_eq!(fw.bytes(), closed[..6].to_vec());
+    }
+
+    #[test]
+    fn list_bulk_extend() {
+        let mut list = nbt::List::Empty;
+        list.try_extend(Vec::<i32>::new()).unwrap();
+        assert_eq!(list, nbt::List::Empty);
+        list.try_extend(0..3).unwrap();
+        list.extend([10, 11]);
+        assert_eq!(list, nbt::List::Int(vec![0, 1, 2, 10, 11]));
+        assert!(matches!(
+            list.try_extend(["a".to_string()]),
+            Err(bin::BinError::HeterogeneousList {
+                list_id: nbt::TAG_INT,
+                element_id: nbt::TAG_STRING
+            })
+        ));
+
+        let mut palette = nbt::List::Empty;
+        palette.extend(
+            (0..2)
+                .map(|i| nbt::Tag::Int(i).wrapped("id".to_string()))
+                .map(|tag| match tag {
+                    nbt::Tag::Compound(map) => map,
+                    _ => unreachable!(),
+                }),
+        );
+        assert_eq!(palette.len(), 2);
+        assert_eq!(palette.element_id(), nbt::TAG_COMPOUND);
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut array = Vec::with_capacity(64);
+        array.extend([1, 2, 3]);
+        let mut inner = Vec::with_capacity(16);
+        inner.push(vec![1i64]);
+        let mut list = nbt::List::LongArray(inner);
+        list.try_extend([Vec::<i64>::with_capacity(32)]).unwrap();
+        let mut tag = nbt::Tag::IntArray(array).wrapped("a".to_string());
+        if let nbt::Tag::Compound(map) = &mut tag {
+            map.insert("b".to_string(), nbt::Tag::List(list));
+        }
+        let before = tag.memory_footprint();
+        let copy = tag.clone();
+        tag.shrink_to_fit();
+        assert_eq!(tag, copy);
+        assert!(tag.memory_footprint() < before);
+        match &tag.as_compound().unwrap()["a"] {
+            nbt::Tag::IntArray(array) => assert_eq!(array.capacity(), 3),
+            _ => unreachable!(),
+        }
+        match tag.get_required_list("b").unwrap() {
+            nbt::List::LongArray(arrays) => {
+                assert_eq!(arrays.capacity(), 2);
+                assert!(arrays.iter().all(|a| a.capacity() == a.len()));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn vanilla_depth_limit() {
+        fn nested(depth: usize) -> Vec<u8> {
+            let mut tag = nbt::Tag::Compound(nbt::Compound::new());
+            for _ in 1..depth {
+                tag = tag.wrapped("a".to_string());
+            }
+            let mut fw = bin::FileWriterBE::new();
+            fw.write_named_tag("", &tag);
+            fw.bytes()
+        }
+        let options = bin::ReadOptions {
+            limits: bin::Limits::vanilla(),
+            ..Default::default()
+        };
+        let read = |bytes: &[u8], options| {
+            nbt::read_named(&mut bin::FileReaderBE::new(bytes, 0).with_options(options))
+        };
+
+        //512 levels of recursion outgrow the 2 MiB of a test thread in debug builds
+        std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(move || {
+                assert!(read(&nested(512), options).is_ok());
+                assert!(matches!(
+                    read(&nested(513), options),
+                    Err(bin::BinError::DepthExceeded { max_depth: 512, .. })
+                ));
+                assert!(read(&nested(513), bin::ReadOptions::default()).is_ok());
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let lists = [
+            &[0x09, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x01][..],
+            &[0x00, 0x00, 0x00, 0x00, 0x00],
+        ]
+        .concat();
+        let shallow = bin::ReadOptions {
+            limits: bin::Limits { max_depth: Some(1) },
+            ..Default::default()
+        };
+        assert!(matches!(
+            read(&lists, shallow),
+            Err(bin::BinError::DepthExceeded {
+                max_depth: 1,
+                pos: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn split_reader() {
+        let bytes = [0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02];
+        let options = bin::ReadOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let mut fr = bin::FileReaderBE::new(&bytes, 0).with_options(options);
+        let mut sub = fr.split_at(4);
+        assert_eq!(sub.read::<i32>().unwrap(), 2);
+        assert!(sub.at_end());
+        assert!(sub.options().strict);
+        assert_eq!(fr.position(), 0);
+        assert_eq!(fr.read::<i32>().unwrap(), 1);
+        assert!(fr.split_at(8).at_end());
+    }
+
+    #[test]
+    fn mismatched_compression_error() {
+        use compression::Compression;
+        let nbt = [0x0A, 0x00, 0x00, 0x00];
+        let e = Compression::GZIP.decode(nbt.to_vec()).unwrap_err();
+        assert_eq!(
+            e.to_string().split(": ").next().unwrap(),
+            "Expected gzip but the buffer starts with [0x0A, 0x00, 0x00, 0x00] (looks like uncompressed NBT)"
+        );
+        let zlib = Compression::ZLIB.encode(nbt.to_vec()).unwrap();
+        let e = Compression::GZIP.decode_slice(&zlib).unwrap_err();
+        assert!(e.to_string().contains("(looks like zlib)"));
+        let gzip = Compression::GZIP.encode(nbt.to_vec()).unwrap();
+        let e = Compression::GZIP
+            .decode_slice(&gzip[..gzip.len() - 4])
+            .unwrap_err();
+        assert!(e
+            .to_string()
+            .contains("(looks like damaged or truncated data)"));
+    }
+
+    #[test]
+    fn tree_string() {
+        let mut level = nbt::Compound::new();
+        level.insert("xPos".to_string(), nbt::Tag::Int(3));
+        level.insert("Heights".to_string(), nbt::Tag::IntArray(vec![0; 256]));
+        let mut root = nbt::Compound::new();
+        root.insert("Level".to_string(), nbt::Tag::Compound(level));
+        root.insert(
+            "Tags".to_string(),
+            nbt::Tag::List(nbt::List::String(vec!["a".to_string()])),
+        );
+        root.insert("Empty".to_string(), nbt::Tag::ByteArray(vec![]));
+        assert_eq!(
+            nbt::Tag::Compound(root).to_tree_string(2),
+            "(Compound)\n\
+             ├─ Empty: ByteArray[0] = []\n\
+             ├─ Level (Compound)\n\
+             │  ├─ Heights: IntArray[256] = [0, 0, … 254 more]\n\
+             │  └─ xPos: Int = 3\n\
+             └─ Tags (List of 1 String)\n   \
+             └─ [0]: String = \"a\""
+        );
+        assert_eq!(nbt::Tag::Long(-1).to_tree_string(0), "Long = -1");
+        assert_eq!(
+            nbt::Tag::LongArray(vec![1, 2]).to_tree_string(0),
+            "LongArray[2] = [… 2 more]"
+        );
+    }
+
+    #[test]
+    fn length_exceeds_input() {
+        let huge = [0x3B, 0x9A, 0xCA, 0x00, 0x00, 0x00, 0x00, 0x01];
+        assert!(matches!(
+            nbt::Tag::read(nbt::TAG_INT_ARRAY, &mut bin::FileReaderBE::new(&huge, 0)),
+            Err(bin::BinError::LengthExceedsInput {
+                len: 1_000_000_000,
+                needed: 4_000_000_000,
+                remaining: 4,
+                pos: 4
+            })
+        ));
+        let longs = [
+            0x04, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+        ];
+        assert!(matches!(
+            nbt::Tag::read(nbt::TAG_LIST, &mut bin::FileReaderBE::new(&longs, 0)),
+            Err(bin::BinError::LengthExceedsInput {
+                needed: 16,
+                remaining: 8,
+                pos: 5,
+                ..
+            })
+        ));
+
+        let mut fr = bin::GrowableReader::new(bin::Endianness::Big);
+        fr.feed(&longs);
+        assert!(fr
+            .try_read(|fr| nbt::Tag::read(nbt::TAG_LIST, fr))
+            .unwrap()
+            .is_none());
+        fr.feed(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02]);
+        assert_eq!(
+            fr.try_read(|fr| nbt::Tag::read(nbt::TAG_LIST, fr)).unwrap(),
+            Some(nbt::Tag::List(nbt::List::Long(vec![1, 2])))
+        );
+    }
+
+    #[test]
+    fn rename_key() {
+        let mut tag = nbt::Tag::Int(1).wrapped("old".to_string());
+        assert!(tag.rename_key("old", "new"));
+        assert_eq!(tag, nbt::Tag::Int(1).wrapped("new".to_string()));
+        assert!(!tag.rename_key("old", "new"));
+        assert!(tag.rename_key("new", "new"));
+        assert_eq!(tag.as_compound().unwrap().len(), 1);
+        assert!(!nbt::Tag::Int(1).rename_key("old", "new"));
+    }
+
+    #[test]
+    fn region_iter_chunks() {
+        let mut region = region::RegionFile::new();
+        for x in 0..3 {
+            let chunk = nbt::Tag::Int(x as i32).wrapped("xPos".to_string());
+            region
+                .write_chunk(x, 0, &chunk, compression::Compression::ZLIB)
+                .unwrap();
+        }
+        let mut bytes = region.bytes();
+        let (offset, _) = region::RegionFile::from_bytes(bytes.clone())
+            .unwrap()
+            .location(1, 0)
+            .unwrap();
+        bytes[offset as usize * region::SECTOR_SIZE + 4] = 0x7F;
+        let region = region::RegionFile::from_bytes(bytes).unwrap();
+
+        let chunks: Vec<_> = region.iter_chunks().collect();
+        assert_eq!(chunks.len(), 3);
+        assert!(
+            matches!(&chunks[0], Ok((0, 0, tag)) if tag.get_required_i32("xPos").unwrap() == 0)
+        );
+        let e = chunks[1].as_ref().unwrap_err();
+        assert!(e.to_string().starts_with("Chunk (1, 0): "));
+        assert!(
+            matches!(&chunks[2], Ok((2, 0, tag)) if tag.get_required_i32("xPos").unwrap() == 2)
+        );
+    }
+
+    #[test]
+    fn canonical_key_order() {
+        use std::cmp::Ordering;
+        assert_eq!(nbt::canonical_key_order("a", "b"), Ordering::Less);
+        assert_eq!(nbt::canonical_key_order("ab", "a"), Ordering::Greater);
+        assert_eq!(nbt::canonical_key_order("é", "é"), Ordering::Equal);
+        assert_eq!(nbt::canonical_key_order("\0", "a"), Ordering::Greater);
+        assert_eq!(
+            nbt::canonical_key_order("\u{1F600}", "\u{FFFD}"),
+            Ordering::Less
+        );
+        assert_eq!(
+            nbt::canonical_key_order("\u{1F600}", "\u{D7FF}"),
+            Ordering::Greater
+        );
+
+        let mut sorted = ["\u{FFFD}", "\0", "a", "\u{1F600}", "\u{E9}", "ab"];
+        sorted.sort_by(|a, b| nbt::canonical_key_order(a, b));
+        let mutf8: Vec<_> = sorted
+            .iter()
+            .map(|key| mutf8::utf8_to_mutf8(key.as_bytes()).unwrap())
+            .collect();
+        assert!(mutf8.windows(2).all(|w| w[0] < w[1]));
+
+        #[cfg(feature = "sorted_compounds")]
+        {
+            let mut map = nbt::Compound::new();
+            for key in ["\0", "a"] {
+                map.insert(key.to_string(), nbt::Tag::Byte(0));
+            }
+            let mut fw = bin::FileWriterBE::new();
+            fw.write(&nbt::Tag::Compound(map));
+            assert_eq!(
+                fw.bytes(),
+                [0x01, 0x00, 0x01, b'a', 0x00, 0x01, 0x00, 0x02, 0xC0, 0x80, 0x00, 0x00]
+            );
+        }
+    }
+
+    #[test]
+    fn take_values() {
+        let sections = nbt::List::Compound(vec![
+            nbt::Tag::Int(0)
+                .wrapped("Y".to_string())
+                .as_compound()
+                .unwrap()
+                .clone(),
+            nbt::Tag::Int(1)
+                .wrapped("Y".to_string())
+                .as_compound()
+                .unwrap()
+                .clone(),
+        ]);
+        let mut tag = nbt::Tag::List(sections).wrapped("sections".to_string());
+        let nbt::Tag::List(mut sections) = tag.take("sections").unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(tag.as_compound().unwrap().len(), 0);
+        assert_eq!(tag.take("sections"), None);
+        assert_eq!(nbt::Tag::Int(0).take("sections"), None);
+
+        assert_eq!(sections.take(2), None);
+        assert_eq!(
+            sections.take(0),
+            Some(nbt::Tag::Int(0).wrapped("Y".to_string()))
+        );
+        assert_eq!(sections.len(), 1);
+        assert_eq!(
+            sections.get(0).unwrap().to_tag(),
+            nbt::Tag::Int(1).wrapped("Y".to_string())
+        );
+        let mut ints = nbt::List::Int(vec![4, 5]);
+        assert_eq!(ints.take(1), Some(nbt::Tag::Int(5)));
+        assert_eq!(nbt::List::Empty.take(0), None);
+    }
+
+    #[test]
+    fn empty_inputs() {
+        assert!(matches!(
+            nbt::read_document(&[]),
+            Err(bin::BinError::EmptyInput)
+        ));
+        assert!(matches!(
+            nbt::Tag::try_from(Vec::new()),
+            Err(bin::BinError::EmptyInput)
+        ));
+
+        let empty = region::RegionFile::from_bytes(Vec::new()).unwrap();
+        assert_eq!(empty.populated_chunks().count(), 0);
+        assert_eq!(empty.iter_chunks().count(), 0);
+        assert!(empty.read_chunk(0, 0).unwrap().is_none());
+        assert!(empty.read_chunk_raw(31, 31).unwrap().is_none());
+        assert_eq!(empty.timestamp(0, 0), None);
+        assert!(region::RegionFile::from_bytes(vec![0x00; 100]).is_err());
+
+        let path = std::env::temp_dir().join("mm_io_empty_inputs.mca");
+        std::fs::write(&path, []).unwrap();
+        let mut fresh = region::RegionFile::open(&path).unwrap();
+        assert_eq!(fresh.populated_chunks().count(), 0);
+        let chunk = nbt::Tag::Int(1).wrapped("x".to_string());
+        fresh
+            .write_chunk(1, 1, &chunk, compression::Compression::ZLIB)
+            .unwrap();
+        assert_eq!(fresh.read_chunk(1, 1).unwrap(), Some(chunk.clone()));
+
+        let mut bytes = fresh.bytes();
+        //offset 2, zero sectors
+        bytes[..4].copy_from_slice(&[0x00, 0x00, 0x02, 0x00]);
+        //zero offset, one sector
+        bytes[4..8].copy_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+        let region = region::RegionFile::from_bytes(bytes).unwrap();
+        for (x, z) in [(0, 0), (1, 0)] {
+            assert!(region.read_chunk(x, z).unwrap().is_none());
+            assert!(region.read_chunk_raw(x, z).unwrap().is_none());
+            assert_eq!(region.timestamp(x, z), None);
+        }
+        assert_eq!(region.populated_chunks().collect::<Vec<_>>(), [(1, 1)]);
+    }
+
+    #[test]
+    fn reserve_policy() {
+        let mut fw = bin::FileWriterBE::new();
+        fw.write(&nbt::Tag::IntArray((0..100).collect()));
+        let bytes = fw.bytes();
+        for (reserve, capacity) in [
+            (bin::ReservePolicy::Exact, 100),
+            (bin::ReservePolicy::Capped(10), 10),
+            (bin::ReservePolicy::Capped(1000), 100),
+            (bin::ReservePolicy::None, 0),
+        ] {
+            let options = bin::ReadOptions {
+                reserve,
+                ..Default::default()
+            };
+            let mut fr = bin::FileReaderBE::new(&bytes, 0).with_options(options);
+            match nbt::Tag::read(nbt::TAG_INT_ARRAY, &mut fr).unwrap() {
+                nbt::Tag::IntArray(array) => {
+                    assert_eq!(array, (0..100).collect::<Vec<_>>());
+                    if capacity == 100 {
+                        assert_eq!(array.capacity(), 100);
+                    } else {
+                        assert!(array.capacity() >= 100);
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+        assert_eq!(
+            bin::ReadOptions::default().reserve,
+            bin::ReservePolicy::Exact
+        );
+    }
+
+    #[test]
+    fn mutf8_len() {
+        for s in [
+            "",
+            "abc",
+            "\0",
+            "é",
+            "\u{FFFD}",
+            "\u{1F600}",
+            "a\0\u{10FFFF}z",
+        ] {
+            assert_eq!(
+                nbt::mutf8_len(s),
+                mutf8::utf8_to_mutf8(s.as_bytes()).unwrap().len()
+            );
+        }
+        assert_eq!(nbt::mutf8_len("\u{1F600}"), 6);
+        assert_eq!("\u{1F600}".len(), 4);
+    }
+
+    #[test]
+    fn bulk_arrays() {
+        let ints: Vec<i32> = (0..5000).map(|i| i * 0x10203 - 7).collect();
+        let longs: Vec<i64> = (0..3000).map(|i| i * 0x1020304050607 - 7).collect();
+        let bytes: Vec<i8> = (0..9000).map(|i| i as i8).collect();
+        let mut root = nbt::Compound::new();
+        root.insert("i".to_string(), nbt::Tag::IntArray(ints.clone()));
+        root.insert("b".to_string(), nbt::Tag::ByteArray(bytes.clone()));
+        root.insert(
+            "f".to_string(),
+            nbt::Tag::List(nbt::List::Float(vec![1.5, -0.0, f32::NAN])),
+        );
+        let bedrock = nbt::Tag::Compound(root.clone());
+        root.insert("l".to_string(), nbt::Tag::LongArray(longs.clone()));
+        root.insert(
+            "a".to_string(),
+            nbt::Tag::List(nbt::List::LongArray(vec![longs.clone(), vec![]])),
+        );
+        let java = nbt::Tag::Compound(root);
+
+        //element by element, the way the scalar path writes them
+        let mut fw = bin::FileWriterBE::new();
+        fw.write(&(ints.len() as i32));
+        fw.write_all(&ints);
+        let mut bulk = bin::FileWriterBE::new();
+        bulk.write(&nbt::Tag::IntArray(ints.clone()));
+        assert_eq!(bulk.bytes(), fw.bytes());
+        let mut fw = bin::FileWriterLE::new();
+        fw.write(&(longs.len() as i32));
+        fw.write_all(&longs);
+        let mut bulk = bin::FileWriterLE::new();
+        bulk.write(&nbt::Tag::LongArray(longs.clone()));
+        assert_eq!(bulk.bytes(), fw.bytes());
+
+        for (flavor, tag) in [
+            (nbt::NbtFlavor::JavaDisk, &java),
+            (nbt::NbtFlavor::BedrockDisk, &bedrock),
+            (nbt::NbtFlavor::BedrockNetwork, &bedrock),
+        ] {
+            let mut fw = bin::FileWriterBE::new();
+            tag.write_with(flavor, "", &mut fw).unwrap();
+            let bytes = fw.bytes();
+            let (_, read) =
+                nbt::Tag::read_with(flavor, &mut bin::FileReaderBE::new(&bytes, 0)).unwrap();
+            assert_eq!(
+                read.to_snbt(&Default::default()),
+                tag.to_snbt(&Default::default())
+            );
+        }
+    }
+
+    #[test]
+    fn prune_empty() {
+        assert!(nbt::Tag::Compound(nbt::Compound::new()).is_empty());
+        assert!(nbt::Tag::List(nbt::List::Int(vec![])).is_empty());
+        assert!(nbt::Tag::List(nbt::List::Empty).is_empty());
+        assert!(nbt::Tag::LongArray(vec![]).is_empty());
+        assert!(!nbt::Tag::String(String::new()).is_empty());
+        assert!(!nbt::Tag::Int(0).is_empty());
+        assert!(!nbt::Tag::List(nbt::List::Int(vec![0])).is_empty());
+
+        let nested_empty = nbt::Tag::Compound(nbt::Compound::new()).wrapped("inner".to_string());
+        let mut root = nbt::Compound::new();
+        root.insert("kept".to_string(), nbt::Tag::Int(1));
+        root.insert("array".to_string(), nbt::Tag::IntArray(vec![]));
+        root.insert("nested".to_string(), nested_empty.clone());
+        root.insert(
+            "lists".to_string(),
+            nbt::Tag::List(nbt::List::List(vec![
+                nbt::List::Empty,
+                nbt::List::Int(vec![1]),
+            ])),
+        );
+        root.insert(
+            "compounds".to_string(),
+            nbt::Tag::List(nbt::List::Compound(vec![nested_empty
+                .as_compound()
+                .unwrap()
+                .clone()])),
+        );
+        let mut tag = nbt::Tag::Compound(root);
+        tag.prune_empty();
+
+        let mut expected = nbt::Compound::new();
+        expected.insert("kept".to_string(), nbt::Tag::Int(1));
+        expected.insert("array".to_string(), nbt::Tag::IntArray(vec![]));
+        expected.insert(
+            "lists".to_string(),
+            nbt::Tag::List(nbt::List::List(vec![nbt::List::Int(vec![1])])),
+        );
+        assert_eq!(tag, nbt::Tag::Compound(expected));
+
+        let mut empty = nested_empty;
+        empty.prune_empty();
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn legacy_name_first() {
+        let tag = nbt::Tag::Int(7).wrapped("x".to_string());
+        let mut fw = bin::FileWriterBE::new();
+        fw.write_named_tag("root", &tag);
+        let mut bytes = fw.bytes();
+        //swap the tag id behind the name: `0A 00 04 root` becomes `00 04 root 0A`
+        bytes[..7].rotate_left(1);
+        let mut fr = bin::FileReaderBE::new(&bytes, 0);
+        assert_eq!(
+            nbt::Tag::read_legacy_name_first(&mut fr).unwrap(),
+            ("root".to_string(), tag)
+        );
+        assert!(fr.at_end());
+        assert!(nbt::read_named(&mut bin::FileReaderBE::new(&bytes, 0)).is_err());
+    }
+
+    #[test]
+    fn into_compressed() {
+        let tag = nbt::Tag::Int(3).wrapped("x".to_string());
+        for compression in [
+            compression::Compression::GZIP,
+            compression::Compression::ZLIB,
+            compression::Compression::Uncompressed,
+        ] {
+            let mut fw = bin::FileWriterBE::new();
+            fw.write_named_tag("", &tag);
+            let uncompressed = fw.bytes();
+            let mut fw = bin::FileWriterBE::new();
+            fw.write_named_tag("", &tag);
+            let bytes = fw.into_compressed(compression).unwrap();
+            assert_eq!(bytes, compression.encode(uncompressed.clone()).unwrap());
+            assert_eq!(nbt::Tag::try_from(bytes.clone()).unwrap(), tag);
+
+            let mut buf = [0x00; 64];
+            let mut fw = bin::SliceWriter::new(&mut buf, bin::Endianness::Big);
+            fw.write_named_tag("", &tag);
+            assert_eq!(fw.into_compressed(compression).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn validate_tag_ids() {
+        for id in 0x01..=0x0C {
+            assert!(nbt::validate_tag_id(id, 0).is_ok());
+        }
+        for id in [0x00, 0x0D, 0xFF] {
+            assert!(matches!(
+                nbt::validate_tag_id(id, 3),
+                Err(bin::BinError::InvalidTagId { tag_id, pos: 3 }) if tag_id == id
+            ));
+        }
+
+        let root = [0x0D, 0x00, 0x00];
+        let entry = [0x0A, 0x00, 0x00, 0x0D, 0x00, 0x00];
+        let element = [0x09, 0x00, 0x00, 0x0D, 0x00, 0x00, 0x00, 0x01];
+        for (bytes, pos) in [(&root[..], 3), (&entry[..], 6), (&element[..], 8)] {
+            let e = nbt::read_named(&mut bin::FileReaderBE::new(bytes, 0)).unwrap_err();
+            assert_eq!(e.to_string(), format!("Invalid Tag ID: 13 at byte {}", pos));
+        }
+    }
+
+    #[test]
+    fn scheme_bytes() {
+        use compression::Compression;
+        let (compression, external) = Compression::from_scheme_byte(2).unwrap();
+        assert_eq!(compression, Compression::ZLIB);
+        assert!(!external);
+        let (compression, external) = Compression::from_scheme_byte(0x83).unwrap();
+        assert_eq!(compression, Compression::Uncompressed);
+        assert!(external);
+        assert!(matches!(
+            Compression::from_scheme_byte(4),
+            Err(bin::BinError::UnknownCompressionScheme(4))
+        ));
+        assert!(matches!(
+            Compression::from_scheme_byte(0x80),
+            Err(bin::BinError::UnknownCompressionScheme(0))
+        ));
+        for b in [1, 2, 3, 0x81, 0x82, 0x83] {
+            let (compression, external) = Compression::from_scheme_byte(b).unwrap();
+            assert_eq!(compression.to_scheme_byte(external), b);
+        }
+    }
+
+    #[test]
+    fn smart_decode() {
+        use compression::{Compression, SmartCompression};
+        let mut fw = bin::FileWriterBE::new();
+        let mut map = nbt::Compound::new();
+        map.insert("a".to_string(), nbt::Tag::Int(1));
+        fw.write_named_tag("", &nbt::Tag::Compound(map));
+        let nbt = fw.bytes();
+        for compression in [
+            Compression::GZIP,
+            Compression::ZLIB,
+            Compression::Uncompressed,
+        ] {
+            let encoded = compression.encode(nbt.clone()).unwrap();
+            let (found, data) = Compression::smart_decode(&encoded).unwrap();
+            assert_eq!(found, SmartCompression::Known(compression));
+            assert_eq!(data, nbt);
+        }
+        let mut deflate =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut deflate, &nbt).unwrap();
+        let deflated = deflate.finish().unwrap();
+        let (found, data) = Compression::smart_decode(&deflated).unwrap();
+        assert_eq!(found, SmartCompression::RawDeflate);
+        assert_eq!(data, nbt);
+        let text = Compression::ZLIB.encode(b"not nbt".to_vec()).unwrap();
+        assert!(Compression::smart_decode(&text).is_err());
+        assert!(Compression::smart_decode(&[0x42, 0x00]).is_err());
+        assert!(Compression::smart_decode(&[]).is_err());
+    }
+
+    #[test]
+    fn array_lists() {
+        let ints = nbt::Tag::List(nbt::List::IntArray(vec![
+            vec![1, -2, 3],
+            vec![],
+            vec![i32::MIN, i32::MAX],
+        ]));
+        let longs = nbt::Tag::List(nbt::List::LongArray(vec![
+            vec![i64::MAX],
+            vec![4, 5, 6, 7],
+            vec![],
+        ]));
+        for (tag, id, inner) in [
+            (&ints, nbt::TAG_INT_ARRAY, 4),
+            (&longs, nbt::TAG_LONG_ARRAY, 8),
+        ] {
+            let arrays = match tag {
+                nbt::Tag::List(nbt::List::IntArray(v)) => {
+                    v.iter().map(|a| a.len()).collect::<Vec<_>>()
+                }
+                nbt::Tag::List(nbt::List::LongArray(v)) => v.iter().map(|a| a.len()).collect(),
+                _ => unreachable!(),
+            };
+            let expected_len = 1 + 4 + arrays.iter().map(|n| 4 + n * inner).sum::<usize>();
+
+            for little in [false, true] {
+                let bytes = if little {
+                    written(bin::FileWriterLE::new(), tag)
+                } else {
+                    written(bin::FileWriterBE::new(), tag)
+                };
+                let int = |v: i32| {
+                    if little {
+                        v.to_le_bytes()
+                    } else {
+                        v.to_be_bytes()
+                    }
+                };
+                assert_eq!(bytes.len(), expected_len);
+                assert_eq!(bytes[0], id);
+                assert_eq!(bytes[1..5], int(3));
+                assert_eq!(bytes[5..9], int(arrays[0] as i32));
+                let (read, end) = if little {
+                    read_list(bin::FileReaderLE::new(&bytes, 0))
+                } else {
+                    read_list(bin::FileReaderBE::new(&bytes, 0))
+                };
+                assert_eq!(&read, tag);
+                assert_eq!(end, bytes.len());
+            }
+        }
+
+        fn written(mut fw: impl FileWriter, tag: &nbt::Tag) -> Vec<u8> {
+            fw.write(tag);
+            fw.bytes()
+        }
+
+        fn read_list(mut fr: impl FileReader) -> (nbt::Tag, usize) {
+            let tag = nbt::Tag::read(nbt::TAG_LIST, &mut fr).unwrap();
+            (tag, fr.position())
+        }
+    }
+
+    #[test]
+    fn coerce_number() {
+        use nbt::Tag;
+        assert_eq!(
+            Tag::Int(1).coerce_number(nbt::TAG_BYTE).unwrap(),
+            Tag::Byte(1)
+        );
+        assert_eq!(
+            Tag::Byte(-3).coerce_number(nbt::TAG_LONG).unwrap(),
+            Tag::Long(-3)
+        );
+        assert_eq!(
+            Tag::Double(2.0).coerce_number(nbt::TAG_SHORT).unwrap(),
+            Tag::Short(2)
+        );
+        assert_eq!(
+            Tag::Int(7).coerce_number(nbt::TAG_FLOAT).unwrap(),
+            Tag::Float(7.0)
+        );
+        assert_eq!(
+            Tag::Float(0.5).coerce_number(nbt::TAG_DOUBLE).unwrap(),
+            Tag::Double(0.5)
+        );
+        assert_eq!(
+            Tag::Short(9).coerce_number(nbt::TAG_SHORT).unwrap(),
+            Tag::Short(9)
+        );
+        assert!(Tag::Int(128).coerce_number(nbt::TAG_BYTE).is_err());
+        let error = Tag::Long(i64::MAX).coerce_number(nbt::TAG_INT).unwrap_err();
+        assert!(matches!(
+            &error,
+            bin::BinError::NumberOutOfRange {
+                value: Tag::Long(i64::MAX),
+                target_id: nbt::TAG_INT,
+            }
+        ));
+        assert!(
+            error.to_string().contains("9223372036854775807"),
+            "{}",
+            error
+        );
+        assert!(Tag::Double(1.5).coerce_number(nbt::TAG_INT).is_err());
+        assert!(Tag::Double(1e300).coerce_number(nbt::TAG_FLOAT).is_err());
+        assert!(matches!(
+            Tag::String("1".to_string()).coerce_number(nbt::TAG_INT),
+            Err(bin::BinError::NotNumeric(nbt::TAG_STRING))
+        ));
+        assert!(matches!(
+            Tag::Int(1).coerce_number(nbt::TAG_STRING),
+            Err(bin::BinError::NotNumeric(nbt::TAG_STRING))
+        ));
+    }
+
+    #[test]
+    fn region_header() {
+        let mut region = region::RegionFile::new();
+        let chunk = nbt::Tag::Compound(nbt::Compound::new());
+        region
+            .write_chunk(3, 1, &chunk, compression::Compression::ZLIB)
+            .unwrap();
+        region.set_timestamp(3, 1, 1234).unwrap();
+        let header = region.header();
+        let slot = header.slot(3, 1).unwrap();
+        assert_eq!(slot.offset, 2);
+        assert_eq!(slot.sectors, 1);
+        assert_eq!(slot.timestamp, 1234);
+        assert!(header.slot(32, 0).is_none());
+        let populated: Vec<_> = header.populated().map(|(x, z, _)| (x, z)).collect();
+        assert_eq!(populated, [(3, 1)]);
+
+        let bytes = region.bytes();
+        assert_eq!(header.to_bytes(), bytes[..region::HEADER_SIZE]);
+        let parsed = region::RegionHeader::from_bytes(&bytes[..region::HEADER_SIZE]).unwrap();
+        assert_eq!(parsed, header);
+        assert!(matches!(
+            region::RegionHeader::from_bytes(&bytes[..100]),
+            Err(bin::BinError::RegionHeaderTooShort { len: 100, .. })
+        ));
+    }
+
+    #[test]
+    fn semantic_eq() {
+        use nbt::{List, Tag};
+        assert_ne!(List::Empty, List::Int(vec![]));
+        assert!(List::Empty.semantic_eq(&List::Int(vec![])));
+        assert!(List::String(vec![]).semantic_eq(&List::Compound(vec![])));
+        assert!(List::Int(vec![1, 2]).semantic_eq(&List::Int(vec![1, 2])));
+        assert!(!List::Int(vec![1]).semantic_eq(&List::Long(vec![1])));
+        assert!(!List::Int(vec![1]).semantic_eq(&List::Empty));
+
+        let nested = List::List(vec![List::Empty, List::Byte(vec![1])]);
+        let typed = List::List(vec![List::Double(vec![]), List::Byte(vec![1])]);
+        assert_ne!(nested, typed);
+        assert!(nested.semantic_eq(&typed));
+
+        let mut a = nbt::Compound::new();
+        a.insert("list".to_string(), Tag::List(nested));
+        let mut b = nbt::Compound::new();
+        b.insert("list".to_string(), Tag::List(typed));
+        let (a, b) = (Tag::Compound(a), Tag::Compound(b));
+        assert!(a.semantic_eq(&b));
+        assert!(!a.semantic_eq(&Tag::Compound(nbt::Compound::new())));
+        assert!(!Tag::Int(1).semantic_eq(&Tag::Long(1)));
+    }
+
+    #[test]
+    fn unsupported_tag_before_payload() {
+        //a long array declaring 1000 longs with none of them there
+        let mut fw = bin::FileWriterLE::new();
+        fw.write(&0x0Au8);
+        fw.write(&String::new());
+        fw.write(&0x0Cu8);
+        fw.write(&"a".to_string());
+        fw.write(&1000i32);
+        fw.write(&0x00u8);
+        let bytes = fw.bytes();
+        let mut fr = bin::FileReaderLE::new(&bytes, 0);
+        assert!(matches!(
+            nbt::Tag::read_with(nbt::NbtFlavor::BedrockDisk, &mut fr),
+            Err(bin::BinError::UnsupportedTag {
+                tag_id: 0x0C,
+                flavor: nbt::NbtFlavor::BedrockDisk,
+                pos: Some(7),
+            })
+        ));
+        assert_eq!(fr.position(), 0);
+        let mut fr = bin::FileReaderLE::new(&bytes, 0);
+        assert!(matches!(
+            nbt::Tag::read_with(nbt::NbtFlavor::JavaDisk, &mut fr),
+            Err(bin::BinError::UnexpectedEndOfByteStream | bin::BinError::LengthExceedsInput { .. })
+        ));
+
+        let list = [
+            0x0A, 0x00, 0x00, 0x09, 0x01, 0x00, b'l', 0x0C, 0x01, 0x00, 0x00, 0x00,
+        ];
+        let mut fr = bin::FileReaderLE::new(&list, 0);
+        assert!(matches!(
+            nbt::Tag::read_with(nbt::NbtFlavor::BedrockDisk, &mut fr),
+            Err(bin::BinError::UnsupportedTag {
+                tag_id: 0x0C,
+                pos: Some(12),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn write_with_streams() {
+        let tag = nbt::Tag::Long(-2).wrapped("l".to_string());
+        for flavor in [nbt::NbtFlavor::JavaDisk, nbt::NbtFlavor::BedrockNetwork] {
+            let mut fw = bin::FileWriterBE::new();
+            tag.write_with(flavor, "r", &mut fw).unwrap();
+            let buffered = fw.bytes();
+            let mut fw = bin::StreamWriter::new(Vec::new(), bin::Endianness::Little);
+            fw.write(&0xFFu8);
+            tag.write_with(flavor, "r", &mut fw).unwrap();
+            let streamed = fw.finish().unwrap();
+            assert_eq!(streamed[1..], buffered[..]);
+            let mut fr = bin::FileReaderBE::new(&streamed, 1);
+            assert_eq!(
+                nbt::Tag::read_with(flavor, &mut fr).unwrap(),
+                ("r".to_string(), tag.clone())
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_string_fields() {
+        let tag: nbt::Tag = [
+            ("name", nbt::Tag::String(r#"{"text":"Steve"}"#.to_string())),
+            ("broken", nbt::Tag::String("{".to_string())),
+            ("count", nbt::Tag::Int(1)),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        assert_eq!(
+            tag.parse_json_string_field("name"),
+            Some(serde_json::json!({ "text": "Steve" }))
+        );
+        assert_eq!(tag.parse_json_string_field("broken"), None);
+        assert_eq!(tag.parse_json_string_field("count"), None);
+        assert_eq!(tag.parse_json_string_field("missing"), None);
+        assert_eq!(nbt::Tag::Int(1).parse_json_string_field("name"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "trace")]
+    fn trace_events() {
+        use std::fmt::Write;
+        use std::sync::{Arc, Mutex};
+        use tracing::{field, span, Event, Metadata};
+
+        struct Events(Arc<Mutex<Vec<String>>>);
+        struct Fields<'a>(&'a mut String);
+
+        impl field::Visit for Fields<'_> {
+            fn record_debug(&mut self, field: &field::Field, value: &dyn std::fmt::Debug) {
+                write!(self.0, "{}={:?} ", field.name(), value).unwrap();
+            }
+        }
+
+        impl tracing::Subscriber for Events {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = String::new();
+                event.record(&mut Fields(&mut fields));
+                self.0.lock().unwrap().push(fields.trim_end().to_string());
+            }
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let tag = nbt::Tag::List(nbt::List::Int(vec![1])).wrapped("l".to_string());
+        let mut fw = bin::FileWriterBE::new();
+        fw.write_named_tag("", &tag);
+        let bytes = fw.bytes();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        tracing::subscriber::with_default(Events(events.clone()), || {
+            nbt::read_named(&mut bin::FileReaderBE::new(&bytes, 0)).unwrap();
+        });
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "message=tag tag_id=10 pos=3 depth=0",
+                "message=compound entry tag_id=9 key=l pos=7",
+                "message=tag tag_id=9 pos=7 depth=1",
+                "message=list list_id=3 len=1 pos=12",
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn read_from_bytes() {
+        let tag = nbt::Tag::Short(7).wrapped("s".to_string());
+        let mut fw = bin::FileWriterLE::new();
+        fw.write_named_tag("", &tag);
+        let packet = bytes::Bytes::from(fw.bytes());
+        let mut fr = bin::FileReaderLE::from_bytes(&packet);
+        assert_eq!(nbt::read_named(&mut fr).unwrap(), (String::new(), tag));
+        assert!(fr.at_end());
+    }
 }
@@ -1,8 +1,70 @@
-use crate::binary::{BinError, BinResult, FileReader, FileWriter, TagIo, Writer};
-use std::{collections::HashMap, fmt::Debug};
+use crate::binary::{
+    encode_mutf8, read_numbers, write_numbers, BinError, BinResult, BulkIo, Endianness, FileReader,
+    FileReaderBE, FileReaderLE, FileWriter, FileWriterBE, FileWriterLE, IntEncoding, Io,
+    ReadOptions, ReframedWriter, ReservePolicy, StreamWriter, TagIo, WriteOptions, Writer,
+};
+use crate::compression::Compression;
+#[cfg(feature = "sorted_compounds")]
+use std::collections::{btree_map::Entry, BTreeMap};
+#[cfg(not(feature = "sorted_compounds"))]
+use std::collections::{hash_map::Entry, HashMap};
+use std::{
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+pub const TAG_END: u8 = 0x00;
+pub const TAG_BYTE: u8 = 0x01;
+pub const TAG_SHORT: u8 = 0x02;
+pub const TAG_INT: u8 = 0x03;
+pub const TAG_LONG: u8 = 0x04;
+pub const TAG_FLOAT: u8 = 0x05;
+pub const TAG_DOUBLE: u8 = 0x06;
+pub const TAG_BYTE_ARRAY: u8 = 0x07;
+pub const TAG_STRING: u8 = 0x08;
+pub const TAG_LIST: u8 = 0x09;
+pub const TAG_COMPOUND: u8 = 0x0A;
+pub const TAG_INT_ARRAY: u8 = 0x0B;
+pub const TAG_LONG_ARRAY: u8 = 0x0C;
+
+///names of the tag ids, indexed by id
+pub const TAG_NAMES: [&str; 13] = [
+    "TAG_End",
+    "TAG_Byte",
+    "TAG_Short",
+    "TAG_Int",
+    "TAG_Long",
+    "TAG_Float",
+    "TAG_Double",
+    "TAG_Byte_Array",
+    "TAG_String",
+    "TAG_List",
+    "TAG_Compound",
+    "TAG_Int_Array",
+    "TAG_Long_Array",
+];
+
+///the map backing [`Tag::Compound`], a `HashMap` unless the `sorted_compounds` feature is enabled,
+///which swaps it for a `BTreeMap` so iteration follows key order and writing follows
+///[`canonical_key_order`]
+#[cfg(not(feature = "sorted_compounds"))]
+pub type Compound = HashMap<String, Tag>;
+#[cfg(feature = "sorted_compounds")]
+pub type Compound = BTreeMap<String, Tag>;
+
+#[cfg(not(feature = "sorted_compounds"))]
+fn compound_with_capacity(capacity: usize) -> Compound {
+    Compound::with_capacity(capacity)
+}
+
+#[cfg(feature = "sorted_compounds")]
+fn compound_with_capacity(_capacity: usize) -> Compound {
+    Compound::new()
+}
 
 #[repr(u8)]
-#[derive(Clone, PartialEq, Debug)]
+#[derive(PartialEq, Debug)]
 pub enum Tag {
     Byte(i8) = 1,
     Short(i16),
@@ -13,82 +75,1370 @@ pub enum Tag {
     ByteArray(Vec<i8>),
     String(String),
     List(List),
-    Compound(HashMap<String, Tag>),
+    Compound(Compound),
     IntArray(Vec<i32>),
     LongArray(Vec<i64>),
 }
 
-#[derive(Clone, PartialEq, Debug)]
-pub enum List {
-    Empty,
-    Byte(Vec<i8>),
-    Short(Vec<i16>),
-    Int(Vec<i32>),
-    Long(Vec<i64>),
-    Float(Vec<f32>),
-    Double(Vec<f64>),
-    ByteArray(Vec<Vec<i8>>),
-    String(Vec<String>),
-    List(Vec<List>),
-    Compound(Vec<HashMap<String, Tag>>),
-    IntArray(Vec<Vec<i32>>),
-    LongArray(Vec<Vec<i64>>),
+#[derive(PartialEq, Debug)]
+pub enum List {
+    Empty,
+    Byte(Vec<i8>),
+    Short(Vec<i16>),
+    Int(Vec<i32>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    ByteArray(Vec<Vec<i8>>),
+    String(Vec<String>),
+    List(Vec<List>),
+    Compound(Vec<Compound>),
+    IntArray(Vec<Vec<i32>>),
+    LongArray(Vec<Vec<i64>>),
+}
+
+impl Clone for Tag {
+    fn clone(&self) -> Self {
+        match self {
+            Tag::Byte(v) => Tag::Byte(*v),
+            Tag::Short(v) => Tag::Short(*v),
+            Tag::Int(v) => Tag::Int(*v),
+            Tag::Long(v) => Tag::Long(*v),
+            Tag::Float(v) => Tag::Float(*v),
+            Tag::Double(v) => Tag::Double(*v),
+            Tag::ByteArray(v) => Tag::ByteArray(v.clone()),
+            Tag::String(v) => Tag::String(v.clone()),
+            Tag::List(v) => Tag::List(v.clone()),
+            Tag::Compound(v) => Tag::Compound(v.clone()),
+            Tag::IntArray(v) => Tag::IntArray(v.clone()),
+            Tag::LongArray(v) => Tag::LongArray(v.clone()),
+        }
+    }
+
+    ///reuses the buffers of `self` wherever its shape matches `source`, down to nested compound
+    ///entries of the same key, so `source.clone_into(&mut tag)` in a loop stops reallocating
+    fn clone_from(&mut self, source: &Self) {
+        match (self, source) {
+            (Tag::ByteArray(dst), Tag::ByteArray(src)) => dst.clone_from(src),
+            (Tag::String(dst), Tag::String(src)) => dst.clone_from(src),
+            (Tag::List(dst), Tag::List(src)) => dst.clone_from(src),
+            (Tag::Compound(dst), Tag::Compound(src)) => clone_compound_from(dst, src),
+            (Tag::IntArray(dst), Tag::IntArray(src)) => dst.clone_from(src),
+            (Tag::LongArray(dst), Tag::LongArray(src)) => dst.clone_from(src),
+            (dst, src) => *dst = src.clone(),
+        }
+    }
+}
+
+impl Clone for List {
+    fn clone(&self) -> Self {
+        match self {
+            List::Empty => List::Empty,
+            List::Byte(v) => List::Byte(v.clone()),
+            List::Short(v) => List::Short(v.clone()),
+            List::Int(v) => List::Int(v.clone()),
+            List::Long(v) => List::Long(v.clone()),
+            List::Float(v) => List::Float(v.clone()),
+            List::Double(v) => List::Double(v.clone()),
+            List::ByteArray(v) => List::ByteArray(v.clone()),
+            List::String(v) => List::String(v.clone()),
+            List::List(v) => List::List(v.clone()),
+            List::Compound(v) => List::Compound(v.clone()),
+            List::IntArray(v) => List::IntArray(v.clone()),
+            List::LongArray(v) => List::LongArray(v.clone()),
+        }
+    }
+
+    ///reuses the buffers of `self` wherever its shape matches `source`, like [`Tag::clone_from`]
+    fn clone_from(&mut self, source: &Self) {
+        match (self, source) {
+            (List::Byte(dst), List::Byte(src)) => dst.clone_from(src),
+            (List::Short(dst), List::Short(src)) => dst.clone_from(src),
+            (List::Int(dst), List::Int(src)) => dst.clone_from(src),
+            (List::Long(dst), List::Long(src)) => dst.clone_from(src),
+            (List::Float(dst), List::Float(src)) => dst.clone_from(src),
+            (List::Double(dst), List::Double(src)) => dst.clone_from(src),
+            (List::ByteArray(dst), List::ByteArray(src)) => dst.clone_from(src),
+            (List::String(dst), List::String(src)) => dst.clone_from(src),
+            (List::List(dst), List::List(src)) => dst.clone_from(src),
+            (List::Compound(dst), List::Compound(src)) => {
+                dst.truncate(src.len());
+                let (init, tail) = src.split_at(dst.len());
+                dst.iter_mut()
+                    .zip(init)
+                    .for_each(|(dst, src)| clone_compound_from(dst, src));
+                dst.extend_from_slice(tail);
+            }
+            (List::IntArray(dst), List::IntArray(src)) => dst.clone_from(src),
+            (List::LongArray(dst), List::LongArray(src)) => dst.clone_from(src),
+            (dst, src) => *dst = src.clone(),
+        }
+    }
+}
+
+///`HashMap::clone_from` clones every value anew, this keeps the values of the keys both share
+fn clone_compound_from(dst: &mut Compound, src: &Compound) {
+    dst.retain(|k, _| src.contains_key(k));
+    for (k, v) in src {
+        match dst.get_mut(k) {
+            Some(dst) => dst.clone_from(v),
+            None => {
+                dst.insert(k.clone(), v.clone());
+            }
+        }
+    }
+}
+
+impl FromIterator<(String, Tag)> for Tag {
+    ///collects key/value pairs into a [`Tag::Compound`]
+    fn from_iter<I: IntoIterator<Item = (String, Tag)>>(iter: I) -> Self {
+        Tag::Compound(iter.into_iter().collect())
+    }
+}
+
+impl Extend<(String, Tag)> for Tag {
+    ///inserts key/value pairs into a compound, replacing existing keys
+    ///
+    ///# Panics
+    ///if the tag isn't a [`Tag::Compound`]
+    fn extend<I: IntoIterator<Item = (String, Tag)>>(&mut self, iter: I) {
+        match self {
+            Tag::Compound(map) => map.extend(iter),
+            _ => panic!(
+                "Can't extend a tag with id {}, only compounds",
+                self.tag_id()
+            ),
+        }
+    }
+}
+
+///a type [`List`] holds elements of, for the bulk [`List::try_extend`]
+pub trait ListElement: Sized {
+    ///tag id of a list of this type
+    const TAG_ID: u8;
+
+    ///the elements of a list of this type, `None` for any other list
+    fn elements_mut(list: &mut List) -> Option<&mut Vec<Self>>;
+
+    fn into_list(elements: Vec<Self>) -> List;
+}
+
+macro_rules! list_element {
+    ($ty:ty, $variant:ident, $id:expr) => {
+        impl ListElement for $ty {
+            const TAG_ID: u8 = $id;
+
+            fn elements_mut(list: &mut List) -> Option<&mut Vec<Self>> {
+                match list {
+                    List::$variant(elements) => Some(elements),
+                    _ => None,
+                }
+            }
+
+            fn into_list(elements: Vec<Self>) -> List {
+                List::$variant(elements)
+            }
+        }
+    };
+}
+
+list_element!(i8, Byte, TAG_BYTE);
+list_element!(i16, Short, TAG_SHORT);
+list_element!(i32, Int, TAG_INT);
+list_element!(i64, Long, TAG_LONG);
+list_element!(f32, Float, TAG_FLOAT);
+list_element!(f64, Double, TAG_DOUBLE);
+list_element!(Vec<i8>, ByteArray, TAG_BYTE_ARRAY);
+list_element!(String, String, TAG_STRING);
+list_element!(List, List, TAG_LIST);
+list_element!(Compound, Compound, TAG_COMPOUND);
+list_element!(Vec<i32>, IntArray, TAG_INT_ARRAY);
+list_element!(Vec<i64>, LongArray, TAG_LONG_ARRAY);
+
+impl<T: ListElement> Extend<T> for List {
+    ///appends the elements, see [`List::try_extend`]
+    ///
+    ///# Panics
+    ///if the list holds another type than `T`
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        if let Err(e) = self.try_extend(iter) {
+            panic!("{}", e);
+        }
+    }
+}
+
+///a borrowed view of a [`Tag`], numbers are copied and everything else is borrowed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TagRef<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'a [i8]),
+    String(&'a str),
+    List(&'a List),
+    Compound(&'a Compound),
+    IntArray(&'a [i32]),
+    LongArray(&'a [i64]),
+}
+
+impl TagRef<'_> {
+    ///id of the viewed tag, like [`Tag::tag_id`]
+    pub fn tag_id(&self) -> u8 {
+        match self {
+            TagRef::Byte(_) => TAG_BYTE,
+            TagRef::Short(_) => TAG_SHORT,
+            TagRef::Int(_) => TAG_INT,
+            TagRef::Long(_) => TAG_LONG,
+            TagRef::Float(_) => TAG_FLOAT,
+            TagRef::Double(_) => TAG_DOUBLE,
+            TagRef::ByteArray(_) => TAG_BYTE_ARRAY,
+            TagRef::String(_) => TAG_STRING,
+            TagRef::List(_) => TAG_LIST,
+            TagRef::Compound(_) => TAG_COMPOUND,
+            TagRef::IntArray(_) => TAG_INT_ARRAY,
+            TagRef::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    ///clones the viewed value into an owned tag
+    pub fn to_tag(&self) -> Tag {
+        match *self {
+            TagRef::Byte(v) => Tag::Byte(v),
+            TagRef::Short(v) => Tag::Short(v),
+            TagRef::Int(v) => Tag::Int(v),
+            TagRef::Long(v) => Tag::Long(v),
+            TagRef::Float(v) => Tag::Float(v),
+            TagRef::Double(v) => Tag::Double(v),
+            TagRef::ByteArray(v) => Tag::ByteArray(v.to_vec()),
+            TagRef::String(v) => Tag::String(v.to_string()),
+            TagRef::List(v) => Tag::List(v.clone()),
+            TagRef::Compound(v) => Tag::Compound(v.clone()),
+            TagRef::IntArray(v) => Tag::IntArray(v.to_vec()),
+            TagRef::LongArray(v) => Tag::LongArray(v.to_vec()),
+        }
+    }
+}
+
+impl<'a> From<&'a Tag> for TagRef<'a> {
+    fn from(tag: &'a Tag) -> Self {
+        tag.as_ref()
+    }
+}
+
+///a tag without any float or double in it, which makes it usable as a `HashMap` key
+///
+///floats have no total equality (`NaN != NaN`), so rather than picking a hashing scheme for
+///them [`HashableTag::new`] refuses any tree containing a [`Tag::Float`], [`Tag::Double`]
+///or a list of either, compounds hash the same whatever the order of their entries
+#[derive(Clone, PartialEq, Debug)]
+pub struct HashableTag(Tag);
+
+impl HashableTag {
+    ///wraps the tag, `None` if it contains floats or doubles
+    pub fn new(tag: Tag) -> Option<Self> {
+        if contains_floats(tag.as_ref()) {
+            return None;
+        }
+        Some(Self(tag))
+    }
+
+    pub fn tag(&self) -> &Tag {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Tag {
+        self.0
+    }
+}
+
+impl Eq for HashableTag {}
+
+impl Hash for HashableTag {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_tag(self.0.as_ref(), state)
+    }
+}
+
+fn contains_floats(tag: TagRef) -> bool {
+    match tag {
+        TagRef::Float(_) | TagRef::Double(_) => true,
+        TagRef::List(List::Float(_) | List::Double(_)) => true,
+        TagRef::List(list) => list.iter().any(contains_floats),
+        TagRef::Compound(map) => map.values().any(|v| contains_floats(v.as_ref())),
+        _ => false,
+    }
+}
+
+fn hash_tag<H: Hasher>(tag: TagRef, state: &mut H) {
+    match tag {
+        TagRef::Byte(v) => (TAG_BYTE, v).hash(state),
+        TagRef::Short(v) => (TAG_SHORT, v).hash(state),
+        TagRef::Int(v) => (TAG_INT, v).hash(state),
+        TagRef::Long(v) => (TAG_LONG, v).hash(state),
+        TagRef::Float(v) => (TAG_FLOAT, v.to_bits()).hash(state),
+        TagRef::Double(v) => (TAG_DOUBLE, v.to_bits()).hash(state),
+        TagRef::ByteArray(v) => (TAG_BYTE_ARRAY, v).hash(state),
+        TagRef::String(v) => (TAG_STRING, v).hash(state),
+        TagRef::List(list) => {
+            (TAG_LIST, list.element_id(), list.len()).hash(state);
+            list.iter().for_each(|element| hash_tag(element, state));
+        }
+        TagRef::Compound(map) => {
+            (TAG_COMPOUND, map.len()).hash(state);
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            for (k, v) in entries {
+                k.hash(state);
+                hash_tag(v.as_ref(), state);
+            }
+        }
+        TagRef::IntArray(v) => (TAG_INT_ARRAY, v).hash(state),
+        TagRef::LongArray(v) => (TAG_LONG_ARRAY, v).hash(state),
+    }
+}
+
+///the value of one of the numeric tags
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Number {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+}
+
+impl Number {
+    ///converts to a double, longs beyond 2^53 lose precision
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            Number::Byte(v) => v as f64,
+            Number::Short(v) => v as f64,
+            Number::Int(v) => v as f64,
+            Number::Long(v) => v as f64,
+            Number::Float(v) => v as f64,
+            Number::Double(v) => v,
+        }
+    }
+
+    ///converts to a long, `None` for non integral or out of range floats
+    pub fn to_i64_checked(&self) -> Option<i64> {
+        match *self {
+            Number::Byte(v) => Some(v as i64),
+            Number::Short(v) => Some(v as i64),
+            Number::Int(v) => Some(v as i64),
+            Number::Long(v) => Some(v),
+            Number::Float(v) => float_to_i64(v as f64),
+            Number::Double(v) => float_to_i64(v),
+        }
+    }
+
+    pub fn to_tag(&self) -> Tag {
+        match *self {
+            Number::Byte(v) => Tag::Byte(v),
+            Number::Short(v) => Tag::Short(v),
+            Number::Int(v) => Tag::Int(v),
+            Number::Long(v) => Tag::Long(v),
+            Number::Float(v) => Tag::Float(v),
+            Number::Double(v) => Tag::Double(v),
+        }
+    }
+}
+
+fn float_to_i64(v: f64) -> Option<i64> {
+    //2^63 is exactly representable, every integral double below it fits
+    if v.fract() != 0.0 || !(-9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0).contains(&v)
+    {
+        return None;
+    }
+    Some(v as i64)
+}
+
+fn refs_semantic_eq(a: TagRef, b: TagRef) -> bool {
+    match (a, b) {
+        (TagRef::List(a), TagRef::List(b)) => a.semantic_eq(b),
+        (TagRef::Compound(a), TagRef::Compound(b)) => {
+            a.len() == b.len()
+                && a.iter().all(|(key, v)| {
+                    b.get(key)
+                        .is_some_and(|w| refs_semantic_eq(v.as_ref(), w.as_ref()))
+                })
+        }
+        (a, b) => a == b,
+    }
+}
+
+impl List {
+    pub fn len(&self) -> usize {
+        match self {
+            List::Empty => 0,
+            List::Byte(list) => list.len(),
+            List::Short(list) => list.len(),
+            List::Int(list) => list.len(),
+            List::Long(list) => list.len(),
+            List::Float(list) => list.len(),
+            List::Double(list) => list.len(),
+            List::ByteArray(list) => list.len(),
+            List::String(list) => list.len(),
+            List::List(list) => list.len(),
+            List::Compound(list) => list.len(),
+            List::IntArray(list) => list.len(),
+            List::LongArray(list) => list.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///compares like `==` except that every empty list is equal, whatever its element type
+    ///
+    ///the derived `PartialEq` is structural, `List::Empty != List::Int(vec![])` even though both
+    ///are written the same way, this holds the same for empty lists nested in the elements
+    pub fn semantic_eq(&self, other: &List) -> bool {
+        if self.is_empty() && other.is_empty() {
+            return true;
+        }
+        match self.element_id() {
+            TAG_LIST | TAG_COMPOUND => {
+                self.element_id() == other.element_id()
+                    && self.len() == other.len()
+                    && self
+                        .iter()
+                        .zip(other.iter())
+                        .all(|(a, b)| refs_semantic_eq(a, b))
+            }
+            _ => self == other,
+        }
+    }
+
+    ///tag id of the list's elements, `0x00` (Tag End) for empty lists
+    pub fn element_id(&self) -> u8 {
+        match self {
+            List::Empty => TAG_END,
+            List::Byte(_) => TAG_BYTE,
+            List::Short(_) => TAG_SHORT,
+            List::Int(_) => TAG_INT,
+            List::Long(_) => TAG_LONG,
+            List::Float(_) => TAG_FLOAT,
+            List::Double(_) => TAG_DOUBLE,
+            List::ByteArray(_) => TAG_BYTE_ARRAY,
+            List::String(_) => TAG_STRING,
+            List::List(_) => TAG_LIST,
+            List::Compound(_) => TAG_COMPOUND,
+            List::IntArray(_) => TAG_INT_ARRAY,
+            List::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    ///borrows the element at `index` as a [`TagRef`]
+    pub fn get(&self, index: usize) -> Option<TagRef<'_>> {
+        match self {
+            List::Empty => None,
+            List::Byte(list) => list.get(index).map(|v| TagRef::Byte(*v)),
+            List::Short(list) => list.get(index).map(|v| TagRef::Short(*v)),
+            List::Int(list) => list.get(index).map(|v| TagRef::Int(*v)),
+            List::Long(list) => list.get(index).map(|v| TagRef::Long(*v)),
+            List::Float(list) => list.get(index).map(|v| TagRef::Float(*v)),
+            List::Double(list) => list.get(index).map(|v| TagRef::Double(*v)),
+            List::ByteArray(list) => list.get(index).map(|v| TagRef::ByteArray(v)),
+            List::String(list) => list.get(index).map(|v| TagRef::String(v)),
+            List::List(list) => list.get(index).map(TagRef::List),
+            List::Compound(list) => list.get(index).map(TagRef::Compound),
+            List::IntArray(list) => list.get(index).map(|v| TagRef::IntArray(v)),
+            List::LongArray(list) => list.get(index).map(|v| TagRef::LongArray(v)),
+        }
+    }
+
+    ///removes the element at `index` and moves it out as a [`Tag`], without cloning it,
+    ///later elements shift down, `None` if `index` is out of bounds
+    pub fn take(&mut self, index: usize) -> Option<Tag> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(match self {
+            List::Empty => return None,
+            List::Byte(list) => Tag::Byte(list.remove(index)),
+            List::Short(list) => Tag::Short(list.remove(index)),
+            List::Int(list) => Tag::Int(list.remove(index)),
+            List::Long(list) => Tag::Long(list.remove(index)),
+            List::Float(list) => Tag::Float(list.remove(index)),
+            List::Double(list) => Tag::Double(list.remove(index)),
+            List::ByteArray(list) => Tag::ByteArray(list.remove(index)),
+            List::String(list) => Tag::String(list.remove(index)),
+            List::List(list) => Tag::List(list.remove(index)),
+            List::Compound(list) => Tag::Compound(list.remove(index)),
+            List::IntArray(list) => Tag::IntArray(list.remove(index)),
+            List::LongArray(list) => Tag::LongArray(list.remove(index)),
+        })
+    }
+
+    ///iterates the elements as [`TagRef`]s, whatever the list's element type is
+    pub fn iter(&self) -> ListIter<'_> {
+        ListIter {
+            list: self,
+            index: 0,
+        }
+    }
+
+    ///iterates the compounds of a list of compounds, `None` for every other list (`List::Empty` included)
+    pub fn compounds(&self) -> Option<impl Iterator<Item = &Compound>> {
+        match self {
+            List::Compound(list) => Some(list.iter()),
+            _ => None,
+        }
+    }
+
+    ///builds a list of lists, every tag has to be a [`Tag::List`]
+    pub fn from_list_tags(tags: Vec<Tag>) -> BinResult<List> {
+        let mut list = List::Empty;
+        for tag in tags {
+            match tag {
+                Tag::List(v) => list.push_list(v)?,
+                tag => return Err(mismatched_element(TAG_LIST, tag.tag_id())),
+            }
+        }
+        Ok(list)
+    }
+
+    ///builds a list of compounds, every tag has to be a [`Tag::Compound`]
+    pub fn from_compound_tags(tags: Vec<Tag>) -> BinResult<List> {
+        let mut list = List::Empty;
+        for tag in tags {
+            match tag {
+                Tag::Compound(v) => list.push_compound(v)?,
+                tag => return Err(mismatched_element(TAG_COMPOUND, tag.tag_id())),
+            }
+        }
+        Ok(list)
+    }
+
+    ///appends a list to a list of lists, an empty list becomes a list of lists
+    pub fn push_list(&mut self, list: List) -> BinResult<()> {
+        match self {
+            List::Empty => *self = List::List(vec![list]),
+            List::List(lists) => lists.push(list),
+            _ => return Err(mismatched_element(self.element_id(), TAG_LIST)),
+        }
+        Ok(())
+    }
+
+    ///appends an array to a list of byte arrays, an empty list becomes a list of byte arrays
+    pub fn push_byte_array(&mut self, array: Vec<i8>) -> BinResult<()> {
+        match self {
+            List::Empty => *self = List::ByteArray(vec![array]),
+            List::ByteArray(arrays) => arrays.push(array),
+            _ => return Err(mismatched_element(self.element_id(), TAG_BYTE_ARRAY)),
+        }
+        Ok(())
+    }
+
+    ///appends an array to a list of int arrays, an empty list becomes a list of int arrays
+    pub fn push_int_array(&mut self, array: Vec<i32>) -> BinResult<()> {
+        match self {
+            List::Empty => *self = List::IntArray(vec![array]),
+            List::IntArray(arrays) => arrays.push(array),
+            _ => return Err(mismatched_element(self.element_id(), TAG_INT_ARRAY)),
+        }
+        Ok(())
+    }
+
+    ///appends an array to a list of long arrays, an empty list becomes a list of long arrays
+    pub fn push_long_array(&mut self, array: Vec<i64>) -> BinResult<()> {
+        match self {
+            List::Empty => *self = List::LongArray(vec![array]),
+            List::LongArray(arrays) => arrays.push(array),
+            _ => return Err(mismatched_element(self.element_id(), TAG_LONG_ARRAY)),
+        }
+        Ok(())
+    }
+
+    ///converts a numeric list to a wider element type without losing precision,
+    ///like a byte list to an int list, to make lists from different sources compatible
+    ///
+    ///integers widen to larger integers, to floats if they fit their mantissa (bytes and shorts)
+    ///and to doubles, floats widen to doubles, anything narrowing or non-numeric is an error,
+    ///converting to the list's own type or converting `List::Empty` gives a copy
+    pub fn widen_to(&self, target_id: u8) -> BinResult<List> {
+        macro_rules! widen {
+            ($list:expr, $variant:ident, $ty:ty) => {
+                List::$variant($list.iter().map(|&v| <$ty>::from(v)).collect())
+            };
+        }
+        let list = match (self, target_id) {
+            (List::Empty, _) => List::Empty,
+            (list, id) if list.element_id() == id => list.clone(),
+            (List::Byte(list), TAG_SHORT) => widen!(list, Short, i16),
+            (List::Byte(list), TAG_INT) => widen!(list, Int, i32),
+            (List::Byte(list), TAG_LONG) => widen!(list, Long, i64),
+            (List::Byte(list), TAG_FLOAT) => widen!(list, Float, f32),
+            (List::Byte(list), TAG_DOUBLE) => widen!(list, Double, f64),
+            (List::Short(list), TAG_INT) => widen!(list, Int, i32),
+            (List::Short(list), TAG_LONG) => widen!(list, Long, i64),
+            (List::Short(list), TAG_FLOAT) => widen!(list, Float, f32),
+            (List::Short(list), TAG_DOUBLE) => widen!(list, Double, f64),
+            (List::Int(list), TAG_LONG) => widen!(list, Long, i64),
+            (List::Int(list), TAG_DOUBLE) => widen!(list, Double, f64),
+            (List::Float(list), TAG_DOUBLE) => widen!(list, Double, f64),
+            (list, id) => {
+                return Err(BinError::IncompatibleListWiden {
+                    from: list.element_id(),
+                    to: id,
+                })
+            }
+        };
+        Ok(list)
+    }
+
+    ///appends every element of `iter`, checking the list's type once rather than per element
+    ///
+    ///an empty list becomes a list of `T` unless `iter` is empty too
+    pub fn try_extend<T: ListElement, I: IntoIterator<Item = T>>(
+        &mut self,
+        iter: I,
+    ) -> BinResult<()> {
+        if *self == List::Empty {
+            let elements: Vec<T> = iter.into_iter().collect();
+            if !elements.is_empty() {
+                *self = T::into_list(elements);
+            }
+            return Ok(());
+        }
+        let list_id = self.element_id();
+        match T::elements_mut(self) {
+            Some(elements) => elements.extend(iter),
+            None => return Err(mismatched_element(list_id, T::TAG_ID)),
+        }
+        Ok(())
+    }
+
+    ///index of the first element matching `pred`
+    pub fn position<F: FnMut(TagRef) -> bool>(&self, pred: F) -> Option<usize> {
+        self.iter().position(pred)
+    }
+
+    ///appends `tag` unless an equal element is already in the list, returning the index of the
+    ///element either way, handy for building palettes
+    ///
+    ///an empty list takes the type of the tag, any other list has to match it
+    pub fn push_unique(&mut self, tag: Tag) -> BinResult<usize> {
+        if let Some(i) = self.position(|element| element == tag.as_ref()) {
+            return Ok(i);
+        }
+        self.push_tag(tag)?;
+        Ok(self.len() - 1)
+    }
+
+    ///moves the elements of `other` to the end of the list, an empty list takes the type of `other`
+    ///
+    ///both lists must hold the same element type, otherwise neither is modified
+    pub fn append(&mut self, other: List) -> BinResult<()> {
+        match (self, other) {
+            (_, List::Empty) => {}
+            (list @ List::Empty, other) => *list = other,
+            (List::Byte(list), List::Byte(mut other)) => list.append(&mut other),
+            (List::Short(list), List::Short(mut other)) => list.append(&mut other),
+            (List::Int(list), List::Int(mut other)) => list.append(&mut other),
+            (List::Long(list), List::Long(mut other)) => list.append(&mut other),
+            (List::Float(list), List::Float(mut other)) => list.append(&mut other),
+            (List::Double(list), List::Double(mut other)) => list.append(&mut other),
+            (List::ByteArray(list), List::ByteArray(mut other)) => list.append(&mut other),
+            (List::String(list), List::String(mut other)) => list.append(&mut other),
+            (List::List(list), List::List(mut other)) => list.append(&mut other),
+            (List::Compound(list), List::Compound(mut other)) => list.append(&mut other),
+            (List::IntArray(list), List::IntArray(mut other)) => list.append(&mut other),
+            (List::LongArray(list), List::LongArray(mut other)) => list.append(&mut other),
+            (list, other) => return Err(mismatched_element(list.element_id(), other.element_id())),
+        }
+        Ok(())
+    }
+
+    fn push_tag(&mut self, tag: Tag) -> BinResult<()> {
+        if *self == List::Empty {
+            *self = match tag {
+                Tag::Byte(_) => List::Byte(vec![]),
+                Tag::Short(_) => List::Short(vec![]),
+                Tag::Int(_) => List::Int(vec![]),
+                Tag::Long(_) => List::Long(vec![]),
+                Tag::Float(_) => List::Float(vec![]),
+                Tag::Double(_) => List::Double(vec![]),
+                Tag::ByteArray(_) => List::ByteArray(vec![]),
+                Tag::String(_) => List::String(vec![]),
+                Tag::List(_) => List::List(vec![]),
+                Tag::Compound(_) => List::Compound(vec![]),
+                Tag::IntArray(_) => List::IntArray(vec![]),
+                Tag::LongArray(_) => List::LongArray(vec![]),
+            };
+        }
+        match (self, tag) {
+            (List::Byte(list), Tag::Byte(v)) => list.push(v),
+            (List::Short(list), Tag::Short(v)) => list.push(v),
+            (List::Int(list), Tag::Int(v)) => list.push(v),
+            (List::Long(list), Tag::Long(v)) => list.push(v),
+            (List::Float(list), Tag::Float(v)) => list.push(v),
+            (List::Double(list), Tag::Double(v)) => list.push(v),
+            (List::ByteArray(list), Tag::ByteArray(v)) => list.push(v),
+            (List::String(list), Tag::String(v)) => list.push(v),
+            (List::List(list), Tag::List(v)) => list.push(v),
+            (List::Compound(list), Tag::Compound(v)) => list.push(v),
+            (List::IntArray(list), Tag::IntArray(v)) => list.push(v),
+            (List::LongArray(list), Tag::LongArray(v)) => list.push(v),
+            (list, tag) => return Err(mismatched_element(list.element_id(), tag.tag_id())),
+        }
+        Ok(())
+    }
+
+    ///shrinks the list and, recursively, its elements to their length, see [`Tag::shrink_to_fit`]
+    pub fn shrink_to_fit(&mut self) {
+        match self {
+            List::Empty => {}
+            List::Byte(list) => list.shrink_to_fit(),
+            List::Short(list) => list.shrink_to_fit(),
+            List::Int(list) => list.shrink_to_fit(),
+            List::Long(list) => list.shrink_to_fit(),
+            List::Float(list) => list.shrink_to_fit(),
+            List::Double(list) => list.shrink_to_fit(),
+            List::ByteArray(list) => shrink_vec(list, Vec::shrink_to_fit),
+            List::String(list) => shrink_vec(list, String::shrink_to_fit),
+            List::List(list) => shrink_vec(list, List::shrink_to_fit),
+            List::Compound(list) => shrink_vec(list, shrink_compound),
+            List::IntArray(list) => shrink_vec(list, Vec::shrink_to_fit),
+            List::LongArray(list) => shrink_vec(list, Vec::shrink_to_fit),
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        match self {
+            List::Empty => 0,
+            List::Byte(list) => vec_heap_size(list),
+            List::Short(list) => vec_heap_size(list),
+            List::Int(list) => vec_heap_size(list),
+            List::Long(list) => vec_heap_size(list),
+            List::Float(list) => vec_heap_size(list),
+            List::Double(list) => vec_heap_size(list),
+            List::ByteArray(list) => {
+                vec_heap_size(list) + list.iter().map(vec_heap_size).sum::<usize>()
+            }
+            List::String(list) => {
+                vec_heap_size(list) + list.iter().map(String::capacity).sum::<usize>()
+            }
+            List::List(list) => {
+                vec_heap_size(list) + list.iter().map(List::heap_size).sum::<usize>()
+            }
+            List::Compound(list) => {
+                vec_heap_size(list) + list.iter().map(compound_heap_size).sum::<usize>()
+            }
+            List::IntArray(list) => {
+                vec_heap_size(list) + list.iter().map(vec_heap_size).sum::<usize>()
+            }
+            List::LongArray(list) => {
+                vec_heap_size(list) + list.iter().map(vec_heap_size).sum::<usize>()
+            }
+        }
+    }
+
+    ///appends a compound to a list of compounds, an empty list becomes a list of compounds
+    pub fn push_compound(&mut self, compound: Compound) -> BinResult<()> {
+        match self {
+            List::Empty => *self = List::Compound(vec![compound]),
+            List::Compound(compounds) => compounds.push(compound),
+            _ => return Err(mismatched_element(self.element_id(), TAG_COMPOUND)),
+        }
+        Ok(())
+    }
+}
+
+fn vec_heap_size<T>(v: &Vec<T>) -> usize {
+    v.capacity() * size_of::<T>()
+}
+
+fn shrink_vec<T>(v: &mut Vec<T>, shrink: impl FnMut(&mut T)) {
+    v.iter_mut().for_each(shrink);
+    v.shrink_to_fit();
+}
+
+fn shrink_compound(map: &mut Compound) {
+    map.values_mut().for_each(Tag::shrink_to_fit);
+    #[cfg(not(feature = "sorted_compounds"))]
+    map.shrink_to_fit();
+}
+
+fn compound_heap_size(map: &Compound) -> usize {
+    map.iter()
+        .map(|(k, v)| size_of::<(String, Tag)>() + 1 + k.capacity() + v.heap_size())
+        .sum()
+}
+
+pub struct ListIter<'a> {
+    list: &'a List,
+    index: usize,
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = TagRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tag = self.list.get(self.index)?;
+        self.index += 1;
+        Some(tag)
+    }
+}
+
+impl<'a> IntoIterator for &'a List {
+    type Item = TagRef<'a>;
+    type IntoIter = ListIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+fn mismatched_element(list_id: u8, element_id: u8) -> BinError {
+    BinError::HeterogeneousList {
+        list_id,
+        element_id,
+    }
+}
+
+pub(crate) fn tag_name(tag_id: u8) -> &'static str {
+    TAG_NAMES
+        .get(tag_id as usize)
+        .copied()
+        .unwrap_or("unknown tag")
+}
+
+impl Tag {
+    pub fn tag_id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => TAG_BYTE,
+            Tag::Short(_) => TAG_SHORT,
+            Tag::Int(_) => TAG_INT,
+            Tag::Long(_) => TAG_LONG,
+            Tag::Float(_) => TAG_FLOAT,
+            Tag::Double(_) => TAG_DOUBLE,
+            Tag::ByteArray(_) => TAG_BYTE_ARRAY,
+            Tag::String(_) => TAG_STRING,
+            Tag::List(_) => TAG_LIST,
+            Tag::Compound(_) => TAG_COMPOUND,
+            Tag::IntArray(_) => TAG_INT_ARRAY,
+            Tag::LongArray(_) => TAG_LONG_ARRAY,
+        }
+    }
+
+    ///borrows the tag as a [`TagRef`] without cloning its contents
+    pub fn as_ref(&self) -> TagRef<'_> {
+        match self {
+            Tag::Byte(v) => TagRef::Byte(*v),
+            Tag::Short(v) => TagRef::Short(*v),
+            Tag::Int(v) => TagRef::Int(*v),
+            Tag::Long(v) => TagRef::Long(*v),
+            Tag::Float(v) => TagRef::Float(*v),
+            Tag::Double(v) => TagRef::Double(*v),
+            Tag::ByteArray(v) => TagRef::ByteArray(v),
+            Tag::String(v) => TagRef::String(v),
+            Tag::List(v) => TagRef::List(v),
+            Tag::Compound(v) => TagRef::Compound(v),
+            Tag::IntArray(v) => TagRef::IntArray(v),
+            Tag::LongArray(v) => TagRef::LongArray(v),
+        }
+    }
+
+    ///gets the entry of a compound's key for in-place manipulation, `None` if the tag isn't a compound
+    pub fn entry(&mut self, key: &str) -> Option<Entry<'_, String, Tag>> {
+        match self {
+            Tag::Compound(map) => Some(map.entry(key.to_string())),
+            _ => None,
+        }
+    }
+
+    ///gets a data component of an item stack compound (1.20.5+), stored under its `components` key
+    ///
+    ///keys without a namespace are looked up in the `minecraft` namespace,
+    ///so `"custom_data"` and `"minecraft:custom_data"` are the same component
+    pub fn get_namespaced(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(map) => match map.get("components")? {
+                Tag::Compound(components) => components.get(&namespaced(key)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    ///inserts a data component into an item stack compound, creating its `components` compound if needed
+    ///
+    ///returns the component previously stored under the key, `None` if the tag isn't a compound
+    pub fn insert_namespaced(&mut self, key: &str, value: Tag) -> Option<Tag> {
+        let components = self
+            .entry("components")?
+            .or_insert_with(|| Tag::Compound(Compound::new()));
+        match components {
+            Tag::Compound(components) => components.insert(namespaced(key), value),
+            _ => None,
+        }
+    }
+
+    ///removes a data component from an item stack compound
+    pub fn remove_namespaced(&mut self, key: &str) -> Option<Tag> {
+        match self {
+            Tag::Compound(map) => match map.get_mut("components")? {
+                Tag::Compound(components) => components.remove(&namespaced(key)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    ///removes a compound's `key` and moves its value out, without cloning it,
+    ///`None` if the key is missing or the tag isn't a compound
+    pub fn take(&mut self, key: &str) -> Option<Tag> {
+        match self {
+            Tag::Compound(map) => map.remove(key),
+            _ => None,
+        }
+    }
+
+    ///moves the value of a compound's `from` key to `to`, replacing whatever `to` held
+    ///
+    ///returns whether the key was there, nothing happens on other tags or a missing `from`
+    pub fn rename_key(&mut self, from: &str, to: &str) -> bool {
+        let Tag::Compound(map) = self else {
+            return false;
+        };
+        if from == to {
+            return map.contains_key(from);
+        }
+        match map.remove(from) {
+            Some(value) => {
+                map.insert(to.to_string(), value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    ///gets the value of a numeric tag, `None` for any other tag
+    pub fn as_number(&self) -> Option<Number> {
+        match *self {
+            Tag::Byte(v) => Some(Number::Byte(v)),
+            Tag::Short(v) => Some(Number::Short(v)),
+            Tag::Int(v) => Some(Number::Int(v)),
+            Tag::Long(v) => Some(Number::Long(v)),
+            Tag::Float(v) => Some(Number::Float(v)),
+            Tag::Double(v) => Some(Number::Double(v)),
+            _ => None,
+        }
+    }
+
+    ///converts a numeric tag to another numeric type, like an int used as a byte flag to a byte
+    ///
+    ///integer targets need an integral value in their range, float and double targets take
+    ///any value in their range, rounding to their precision, out of range values are a
+    ///[`BinError::NumberOutOfRange`] rather than wrapping, non-numeric tags or targets
+    ///a [`BinError::NotNumeric`]
+    pub fn coerce_number(&self, target_id: u8) -> BinResult<Tag> {
+        let number = self
+            .as_number()
+            .ok_or(BinError::NotNumeric(self.tag_id()))?;
+        let out_of_range = || BinError::NumberOutOfRange {
+            value: self.clone(),
+            target_id,
+        };
+        let integer = || number.to_i64_checked().ok_or_else(out_of_range);
+        Ok(match target_id {
+            TAG_BYTE => Tag::Byte(integer()?.try_into().map_err(|_| out_of_range())?),
+            TAG_SHORT => Tag::Short(integer()?.try_into().map_err(|_| out_of_range())?),
+            TAG_INT => Tag::Int(integer()?.try_into().map_err(|_| out_of_range())?),
+            TAG_LONG => Tag::Long(integer()?),
+            TAG_FLOAT => match number.to_f64() {
+                v if v.is_finite() && v.abs() > f32::MAX as f64 => return Err(out_of_range()),
+                v => Tag::Float(v as f32),
+            },
+            TAG_DOUBLE => Tag::Double(number.to_f64()),
+            id => return Err(BinError::NotNumeric(id)),
+        })
+    }
+
+    ///compares like `==` except that empty lists are equal whatever their element type,
+    ///see [`List::semantic_eq`]
+    pub fn semantic_eq(&self, other: &Tag) -> bool {
+        refs_semantic_eq(self.as_ref(), other.as_ref())
+    }
+
+    ///borrows the entries of a compound, `None` for any other tag
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Tag::Compound(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    ///mutably borrows the entries of a compound to edit them in place, `None` for any other tag
+    pub fn as_compound_mut(&mut self) -> Option<&mut Compound> {
+        match self {
+            Tag::Compound(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    ///borrows the list, `None` for any other tag
+    pub fn as_list(&self) -> Option<&List> {
+        match self {
+            Tag::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    ///mutably borrows the list to edit it in place, `None` for any other tag
+    pub fn as_list_mut(&mut self) -> Option<&mut List> {
+        match self {
+            Tag::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    ///whether the tag is a container without anything in it: a compound without entries,
+    ///a list without elements (whatever its element type) or an array of length 0,
+    ///numbers and strings, even `""`, are never empty
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Tag::Compound(map) => map.is_empty(),
+            Tag::List(list) => list.is_empty(),
+            Tag::ByteArray(v) => v.is_empty(),
+            Tag::IntArray(v) => v.is_empty(),
+            Tag::LongArray(v) => v.is_empty(),
+            _ => false,
+        }
+    }
+
+    ///removes every empty compound and list of the tree, compound values and list elements alike,
+    ///children are pruned first so containers only holding empty ones are removed too
+    ///
+    ///empty arrays are kept, as is the tag itself even if it ends up empty
+    pub fn prune_empty(&mut self) {
+        match self {
+            Tag::Compound(map) => prune_compound(map),
+            Tag::List(list) => prune_list(list),
+            _ => {}
+        }
+    }
+
+    ///keeps only the compound entries for which `f` returns true, no-op on other tags
+    pub fn retain<F: FnMut(&str, &Tag) -> bool>(&mut self, mut f: F) {
+        if let Tag::Compound(map) = self {
+            map.retain(|k, v| f(k, v));
+        }
+    }
+
+    ///like [`Tag::retain`] but applied to every compound of the tree, lists of compounds included
+    ///
+    ///entries are filtered before their children, so removed subtrees are never visited
+    pub fn retain_recursive<F: FnMut(&str, &Tag) -> bool>(&mut self, mut f: F) {
+        retain_recursive(self, &mut f)
+    }
+
+    ///lists every leaf of the tree along with its path, compounds and lists aren't listed themselves
+    ///
+    ///paths follow the vanilla NBT path syntax: `Inventory[0].tag.display`, keys that aren't
+    ///made of `[A-Za-z0-9_+-]` are quoted, a root that isn't a compound or list has the path `""`
+    pub fn flatten(&self) -> Vec<(String, Tag)> {
+        let mut leaves = Vec::new();
+        flatten_into(self.as_ref(), &mut String::new(), &mut leaves);
+        leaves
+    }
+
+    ///checks the whole tree can be written as valid NBT, reporting every problem found
+    ///
+    ///lists are homogeneous by construction, so what's left to check is that strings and keys
+    ///fit their `u16` length prefix once encoded as ``MUTF-8`` and that arrays and lists fit
+    ///their `i32` one, paths use the syntax of [`Tag::flatten`]
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        validate_into(self.as_ref(), &mut String::new(), &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    ///estimates the bytes the tag takes up in memory, the tag itself plus everything it owns
+    ///
+    ///vectors and strings count their capacity, compound entries are counted as their key, value
+    ///and a byte of map overhead, so the real figure can differ depending on the map's load factor
+    pub fn memory_footprint(&self) -> usize {
+        size_of::<Tag>() + self.heap_size()
+    }
+
+    ///counts the tags of the tree: the tag itself plus, recursively, every compound value and
+    ///every list element, arrays and strings are a single node whatever their length
+    ///
+    ///`{a: [1, 2], b: [I; 1, 2, 3]}` has 5 nodes: the compound, the list, its two ints and the array
+    pub fn count_nodes(&self) -> usize {
+        count_nodes(self.as_ref())
+    }
+
+    ///shrinks the capacity of every string, array, list and compound of the tree to its length,
+    ///for trees kept around long after parsing, like a cache of chunks
+    ///
+    ///this is a deep operation visiting every node, compound keys can't be changed in place and
+    ///keep their capacity, a `BTreeMap` compound (`sorted_compounds`) has no capacity to shrink
+    pub fn shrink_to_fit(&mut self) {
+        match self {
+            Tag::ByteArray(v) => v.shrink_to_fit(),
+            Tag::String(v) => v.shrink_to_fit(),
+            Tag::List(list) => list.shrink_to_fit(),
+            Tag::Compound(map) => shrink_compound(map),
+            Tag::IntArray(v) => v.shrink_to_fit(),
+            Tag::LongArray(v) => v.shrink_to_fit(),
+            _ => {}
+        }
+    }
+
+    fn heap_size(&self) -> usize {
+        match self {
+            Tag::ByteArray(v) => vec_heap_size(v),
+            Tag::String(v) => v.capacity(),
+            Tag::List(list) => list.heap_size(),
+            Tag::Compound(map) => compound_heap_size(map),
+            Tag::IntArray(v) => vec_heap_size(v),
+            Tag::LongArray(v) => vec_heap_size(v),
+            _ => 0,
+        }
+    }
+
+    pub fn byte_array_with_capacity(capacity: usize) -> Self {
+        Tag::ByteArray(Vec::with_capacity(capacity))
+    }
+
+    pub fn int_array_with_capacity(capacity: usize) -> Self {
+        Tag::IntArray(Vec::with_capacity(capacity))
+    }
+
+    pub fn long_array_with_capacity(capacity: usize) -> Self {
+        Tag::LongArray(Vec::with_capacity(capacity))
+    }
+
+    ///wraps the tag in a compound with it's key/name set to `k`
+    pub fn wrapped(self, k: String) -> Self {
+        let mut buf = Compound::new();
+        buf.insert(k, self);
+        Tag::Compound(buf)
+    }
+}
+
+fn flatten_into(tag: TagRef, path: &mut String, leaves: &mut Vec<(String, Tag)>) {
+    let len = path.len();
+    match tag {
+        TagRef::Compound(map) => {
+            for (k, v) in map {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                push_path_key(k, path);
+                flatten_into(v.as_ref(), path, leaves);
+                path.truncate(len);
+            }
+        }
+        TagRef::List(list) => {
+            for (i, element) in list.iter().enumerate() {
+                path.push_str(&format!("[{}]", i));
+                flatten_into(element, path, leaves);
+                path.truncate(len);
+            }
+        }
+        leaf => leaves.push((path.clone(), leaf.to_tag())),
+    }
+}
+
+///a problem found by [`Tag::validate`]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ValidationError {
+    ///path of the offending tag, empty for the root
+    pub path: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+fn validate_into(tag: TagRef, path: &mut String, errors: &mut Vec<ValidationError>) {
+    let mut error = |message: String| {
+        errors.push(ValidationError {
+            path: path.clone(),
+            message,
+        })
+    };
+    let len = match tag {
+        TagRef::ByteArray(v) => v.len(),
+        TagRef::IntArray(v) => v.len(),
+        TagRef::LongArray(v) => v.len(),
+        TagRef::List(list) => list.len(),
+        TagRef::String(v) => {
+            let len = mutf8_len(v);
+            if len > u16::MAX as usize {
+                error(format!("String is {} bytes long, at most 65535 fit", len));
+            }
+            0
+        }
+        _ => 0,
+    };
+    if len > i32::MAX as usize {
+        error(format!("{} elements don't fit an i32 length", len));
+    }
+
+    let path_len = path.len();
+    match tag {
+        TagRef::Compound(map) => {
+            for (k, v) in map {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                push_path_key(k, path);
+                let len = mutf8_len(k);
+                if len > u16::MAX as usize {
+                    errors.push(ValidationError {
+                        path: path.clone(),
+                        message: format!("Key is {} bytes long, at most 65535 fit", len),
+                    });
+                }
+                validate_into(v.as_ref(), path, errors);
+                path.truncate(path_len);
+            }
+        }
+        TagRef::List(list) => {
+            for (i, element) in list.iter().enumerate() {
+                path.push_str(&format!("[{}]", i));
+                validate_into(element, path, errors);
+                path.truncate(path_len);
+            }
+        }
+        _ => {}
+    }
+}
+
+///length of a string once encoded as ``MUTF-8``, what its `u16` length prefix counts and
+///what has to stay within 65535: nul is 2 bytes instead of 1, supplementary characters are two
+///3 byte surrogates instead of 4 bytes, everything else matches [`str::len`]
+///
+///ASCII strings without nul are measured without being copied
+pub fn mutf8_len(s: &str) -> usize {
+    encode_mutf8(s).len()
+}
+
+///the order [`Compound`] keys are written in with the `sorted_compounds` feature:
+///lexicographic over their ``MUTF-8`` bytes, the bytes actually written to the file
+///
+///this matches comparing `&str`s (UTF-8 bytes, or code points) except for two cases:
+///nul is encoded as `C0 80` so it sorts after every other ASCII character rather than before,
+///and supplementary characters (U+10000 and up) are encoded as surrogates starting `ED A0`..`ED AF`,
+///so they sort between U+D7FF and U+E000 rather than after U+FFFF
+pub fn canonical_key_order(a: &str, b: &str) -> std::cmp::Ordering {
+    encode_mutf8(a).cmp(&encode_mutf8(b))
+}
+
+fn count_nodes(tag: TagRef) -> usize {
+    1 + match tag {
+        TagRef::List(list) => list.iter().map(count_nodes).sum(),
+        TagRef::Compound(map) => map.values().map(|v| count_nodes(v.as_ref())).sum(),
+        _ => 0,
+    }
+}
+
+fn push_path_key(key: &str, path: &mut String) {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-'))
+    {
+        path.push_str(key);
+        return;
+    }
+    path.push('"');
+    for c in key.chars() {
+        if c == '"' || c == '\\' {
+            path.push('\\');
+        }
+        path.push(c);
+    }
+    path.push('"');
+}
+
+fn prune_compound(map: &mut Compound) {
+    map.retain(|_, v| {
+        v.prune_empty();
+        !(matches!(v, Tag::Compound(_) | Tag::List(_)) && v.is_empty())
+    });
 }
 
-impl List {
-    pub fn len(&self) -> usize {
-        match self {
-            List::Empty => 0,
-            List::Byte(list) => list.len(),
-            List::Short(list) => list.len(),
-            List::Int(list) => list.len(),
-            List::Long(list) => list.len(),
-            List::Float(list) => list.len(),
-            List::Double(list) => list.len(),
-            List::ByteArray(list) => list.len(),
-            List::String(list) => list.len(),
-            List::List(list) => list.len(),
-            List::Compound(list) => list.len(),
-            List::IntArray(list) => list.len(),
-            List::LongArray(list) => list.len(),
-        }
+fn prune_list(list: &mut List) {
+    match list {
+        List::Compound(maps) => maps.retain_mut(|map| {
+            prune_compound(map);
+            !map.is_empty()
+        }),
+        List::List(lists) => lists.retain_mut(|list| {
+            prune_list(list);
+            !list.is_empty()
+        }),
+        _ => {}
     }
 }
 
-impl Tag {
-    fn tag_id(&self) -> u8 {
-        match self {
-            Tag::Byte(_) => 0x01,
-            Tag::Short(_) => 0x02,
-            Tag::Int(_) => 0x03,
-            Tag::Long(_) => 0x04,
-            Tag::Float(_) => 0x05,
-            Tag::Double(_) => 0x06,
-            Tag::ByteArray(_) => 0x07,
-            Tag::String(_) => 0x08,
-            Tag::List(_) => 0x09,
-            Tag::Compound(_) => 0x0A,
-            Tag::IntArray(_) => 0x0B,
-            Tag::LongArray(_) => 0x0C,
-        }
+fn retain_recursive<F: FnMut(&str, &Tag) -> bool>(tag: &mut Tag, f: &mut F) {
+    match tag {
+        Tag::Compound(map) => retain_compound(map, f),
+        Tag::List(list) => retain_list(list, f),
+        _ => {}
     }
+}
 
-    ///wraps the tag in a compound with it's key/name set to `k`
-    pub fn wrapped(self, k: String) -> Self {
-        let mut buf = HashMap::<String, Tag>::new();
-        buf.insert(k, self);
-        Tag::Compound(buf)
+fn retain_compound<F: FnMut(&str, &Tag) -> bool>(map: &mut Compound, f: &mut F) {
+    map.retain(|k, v| f(k, v));
+    for v in map.values_mut() {
+        retain_recursive(v, f);
+    }
+}
+
+fn retain_list<F: FnMut(&str, &Tag) -> bool>(list: &mut List, f: &mut F) {
+    match list {
+        List::Compound(maps) => maps.iter_mut().for_each(|map| retain_compound(map, f)),
+        List::List(lists) => lists.iter_mut().for_each(|list| retain_list(list, f)),
+        _ => {}
     }
 }
 
+///prefixes keys without a namespace with `minecraft:`
+fn namespaced(key: &str) -> String {
+    if key.contains(':') {
+        key.to_string()
+    } else {
+        format!("minecraft:{}", key)
+    }
+}
+
+///a `tracing` event with the `trace` feature, nothing at all without it
+macro_rules! trace {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "trace")]
+        tracing::trace!($($arg)*);
+    }};
+}
+
 macro_rules! read_array {
-    ($fr:expr) => {{
+    ($fr:expr, $size:expr) => {{
         let len: i32 = $fr.read()?;
-        let mut array = Vec::new();
-        for _ in 0..len {
-            array.push($fr.read()?);
-        }
-        array
+        read_number_list(len, $size, $fr)?
     }};
 }
 
@@ -100,136 +1450,745 @@ macro_rules! read_list {
         }
         array
     }};
+    ($len:expr, $fr:expr, $size:expr) => {
+        read_number_list($len, $size, $fr)?
+    };
 }
 
 macro_rules! read_list_array {
-    ($len:expr, $fr:expr) => {{
-        let mut array = Vec::new();
+    ($len:expr, $fr:expr, $size:expr) => {{
+        check_len($len, element_size($fr, 4, true), $fr)?;
+        let mut array = Vec::with_capacity(reserved($len, $fr));
         for _ in 0..$len {
-            let len_: i32 = $fr.read()?;
-            let mut array_ = Vec::new();
-            for _ in 0..len_ {
-                array_.push($fr.read()?);
-            }
-            array.push(array_);
+            array.push(read_array!($fr, $size));
         }
         array
     }};
 }
 
-fn read_list(list_id: u8, fr: &mut impl FileReader) -> BinResult<List> {
-    let len : i32 = fr.read()?;
+///errors before reading `len` elements of at least `size` bytes each if the reader doesn't
+///have that many bytes left, rather than running out partway through
+fn check_len(len: i32, size: usize, fr: &impl FileReader) -> BinResult<()> {
+    let remaining = fr.buffer().len().saturating_sub(fr.position());
+    let needed = (len.max(0) as usize).saturating_mul(size);
+    if needed > remaining {
+        return Err(BinError::LengthExceedsInput {
+            len,
+            needed,
+            remaining,
+            pos: fr.position(),
+        });
+    }
+    Ok(())
+}
+
+///reads `len` numbers of at least `size` bytes each, see [`check_len`] and [`reserved`]
+fn read_number_list<T: BulkIo>(
+    len: i32,
+    size: usize,
+    fr: &mut impl FileReader,
+) -> BinResult<Vec<T>> {
+    check_len(len, size, fr)?;
+    let mut array = Vec::with_capacity(reserved(len, fr));
+    read_numbers(fr, len.max(0) as usize, &mut array)?;
+    Ok(array)
+}
+
+///the capacity reserved for a list or array of `len` elements, see [`ReservePolicy`]
+fn reserved(len: i32, fr: &impl FileReader) -> usize {
+    let len = len.max(0) as usize;
+    match fr.options().reserve {
+        ReservePolicy::Exact => len,
+        ReservePolicy::Capped(cap) => len.min(cap),
+        ReservePolicy::None => 0,
+    }
+}
+
+///the fewest bytes an element of `size` bytes can take, ints and longs being a single byte
+///when they're varints
+fn element_size(fr: &impl FileReader, size: usize, int: bool) -> usize {
+    if int && fr.options().ints == IntEncoding::VarInt {
+        1
+    } else {
+        size
+    }
+}
+
+///lists with a length of 0 or less are read as `List::Empty` whatever their element type,
+///strict mode only accepts `TAG_End` for them
+fn read_list(fr: &mut impl FileReader, depth: usize) -> BinResult<List> {
+    let depth = enter_container(fr, depth)?;
+    let list_id: u8 = fr.read()?;
+    let len: i32 = fr.read()?;
+    trace!(list_id, len, pos = fr.position(), "list");
     if len <= 0 {
-        return Ok(List::Empty)
+        if fr.options().strict && list_id != TAG_END {
+            return Err(BinError::TypedEmptyList(list_id));
+        }
+        return Ok(List::Empty);
     }
+    check_flavor(list_id, fr)?;
     match list_id {
-        0x00 => {
+        TAG_END => {
             if len > 0 {
-                return Err(BinError::Parsing(
-                        r#"Lists of type "Tag End" can't have a length greater than 0"#.to_string()
-                ))
+                return Err(BinError::NonEmptyEndList(len));
             }
             Ok(List::Empty)
-        },
-        0x01 => Ok(List::Byte(read_list!(len, fr))),
-        0x02 => Ok(List::Short(read_list!(len, fr))),
-        0x03 => Ok(List::Int(read_list!(len, fr))),
-        0x04 => Ok(List::Long(read_list!(len, fr))),
-        0x05 => Ok(List::Float(read_list!(len, fr))),
-        0x06 => Ok(List::Double(read_list!(len, fr))),
-        0x07 => Ok(List::ByteArray(read_list_array!(len, fr))),
-        0x08 => Ok(List::String(read_list!(len, fr))),
-        0x09 => {
-            let len: i32 = fr.read()?;
+        }
+        TAG_BYTE => Ok(List::Byte(read_list!(len, fr, 1))),
+        TAG_SHORT => Ok(List::Short(read_list!(len, fr, 2))),
+        TAG_INT => Ok(List::Int(read_list!(len, fr, element_size(fr, 4, true)))),
+        TAG_LONG => Ok(List::Long(read_list!(len, fr, element_size(fr, 8, true)))),
+        TAG_FLOAT => Ok(List::Float(read_list!(len, fr, 4))),
+        TAG_DOUBLE => Ok(List::Double(read_list!(len, fr, 8))),
+        TAG_BYTE_ARRAY => Ok(List::ByteArray(read_list_array!(len, fr, 1))),
+        TAG_STRING => Ok(List::String(read_list!(len, fr))),
+        TAG_LIST => {
             let mut array = Vec::new();
             for _ in 0..len {
-                array.push(read_list(fr.read()?, fr)?);
+                array.push(read_list(fr, depth)?);
             }
             Ok(List::List(array))
         }
-        0x0A => {
-            let len: i32 = fr.read()?;
+        TAG_COMPOUND => {
             let mut array = Vec::new();
             for _ in 0..len {
-                array.push(read_compound(fr)?);
+                array.push(read_compound(fr, depth)?);
             }
             Ok(List::Compound(array))
         }
-        0x0B => Ok(List::IntArray(read_list_array!(len, fr))),
-        0x0C => Ok(List::LongArray(read_list_array!(len, fr))),
-        x => Err(BinError::Parsing(format!("Invalid Tag ID: {}", x))),
+        TAG_INT_ARRAY => Ok(List::IntArray(read_list_array!(
+            len,
+            fr,
+            element_size(fr, 4, true)
+        ))),
+        TAG_LONG_ARRAY => Ok(List::LongArray(read_list_array!(
+            len,
+            fr,
+            element_size(fr, 8, true)
+        ))),
+        x => {
+            if let Err(e) = validate_tag_id(x, fr.position()) {
+                skip_unknown(x, len as usize, fr, e)?;
+            }
+            Ok(List::Empty)
+        }
+    }
+}
+
+///skips `count` payloads of an unknown tag id using `ReadOptions::unknown_tag_size`,
+///returning `invalid`, the error of [`validate_tag_id`], if it can't tell their size
+fn skip_unknown(
+    tag_id: u8,
+    count: usize,
+    fr: &mut impl FileReader,
+    invalid: BinError,
+) -> BinResult<()> {
+    match fr.options().unknown_tag_size.and_then(|size| size(tag_id)) {
+        Some(size) => {
+            let len = size
+                .checked_mul(count)
+                .ok_or(BinError::UnexpectedEndOfByteStream)?;
+            fr.get_slice(len)?;
+            Ok(())
+        }
+        None => Err(invalid),
+    }
+}
+
+///checks `tag_id` is one of the tags `0x01..=0x0C`, `pos` being where its payload would start
+///
+///every id read goes through it, roots, compound entries and list elements alike (lists
+///accepting Tag End on top), so a bad id is the same [`BinError::InvalidTagId`] wherever it's hit
+pub fn validate_tag_id(tag_id: u8, pos: usize) -> BinResult<()> {
+    if (TAG_BYTE..=TAG_LONG_ARRAY).contains(&tag_id) {
+        Ok(())
+    } else {
+        Err(BinError::InvalidTagId { tag_id, pos })
+    }
+}
+
+///errors on known ids the [`ReadOptions::flavor`] doesn't have, ids above `0x0C` are left to
+///[`validate_tag_id`]
+fn check_flavor(tag_id: u8, fr: &impl FileReader) -> BinResult<()> {
+    match fr.options().flavor {
+        Some(flavor) if tag_id <= TAG_LONG_ARRAY && !flavor.supports_tag_id(tag_id) => {
+            Err(BinError::UnsupportedTag {
+                tag_id,
+                flavor,
+                pos: Some(fr.position()),
+            })
+        }
+        _ => Ok(()),
     }
 }
 
-fn read_compound(fr: &mut impl FileReader) -> BinResult<HashMap<String, Tag>> {
-    let mut buf = HashMap::<String, Tag>::new();
-    while !fr.at_end() {
+///reads entries up to and including the Tag End closing the compound,
+///running out of bytes before it is an error rather than the end of the compound
+fn read_compound(fr: &mut impl FileReader, depth: usize) -> BinResult<Compound> {
+    let depth = enter_container(fr, depth)?;
+    let mut buf = compound_with_capacity(fr.options().compound_capacity);
+    loop {
         let tag_id: u8 = fr.read()?;
-        if tag_id == 0x00 {
+        if tag_id == TAG_END {
             break;
         }
-        buf.insert(fr.read()?, Tag::read(tag_id, fr)?);
+        let key: String = fr.read()?;
+        trace!(tag_id, key = %key, pos = fr.position(), "compound entry");
+        if let Err(e) = validate_tag_id(tag_id, fr.position()) {
+            skip_unknown(tag_id, 1, fr, e)?;
+            continue;
+        }
+        let tag = read_tag(tag_id, fr, depth)?;
+        if fr.options().strict && buf.contains_key(&key) {
+            return Err(BinError::DuplicateKey(key));
+        }
+        buf.insert(key, tag);
     }
     Ok(buf)
 }
 
+///the depth of the list or compound about to be read, erroring past `ReadOptions::limits`
+fn enter_container(fr: &impl FileReader, depth: usize) -> BinResult<usize> {
+    let depth = depth + 1;
+    match fr.options().limits.max_depth {
+        Some(max_depth) if depth > max_depth => Err(BinError::DepthExceeded {
+            max_depth,
+            pos: fr.position(),
+        }),
+        _ => Ok(depth),
+    }
+}
+
+///reads a payload nested in `depth` lists and compounds
+fn read_tag(tag_id: u8, fr: &mut impl FileReader, depth: usize) -> BinResult<Tag> {
+    trace!(tag_id, pos = fr.position(), depth, "tag");
+    validate_tag_id(tag_id, fr.position())?;
+    check_flavor(tag_id, fr)?;
+    match tag_id {
+        TAG_BYTE => Ok(Tag::Byte(fr.read()?)),
+        TAG_SHORT => Ok(Tag::Short(fr.read()?)),
+        TAG_INT => Ok(Tag::Int(fr.read()?)),
+        TAG_LONG => Ok(Tag::Long(fr.read()?)),
+        TAG_FLOAT => Ok(Tag::Float(fr.read()?)),
+        TAG_DOUBLE => Ok(Tag::Double(fr.read()?)),
+        TAG_BYTE_ARRAY => Ok(Tag::ByteArray(read_array!(fr, 1))),
+        TAG_STRING => Ok(Tag::String(fr.read()?)),
+        TAG_LIST => Ok(Tag::List(read_list(fr, depth)?)),
+        TAG_COMPOUND => Ok(Tag::Compound(read_compound(fr, depth)?)),
+        TAG_INT_ARRAY => Ok(Tag::IntArray(read_array!(fr, element_size(fr, 4, true)))),
+        TAG_LONG_ARRAY => Ok(Tag::LongArray(read_array!(fr, element_size(fr, 8, true)))),
+        _ => unreachable!("validate_tag_id only accepts 0x01..=0x0C"),
+    }
+}
+
 impl TagIo for Tag {
     fn read(tag_id: u8, fr: &mut impl FileReader) -> BinResult<Self> {
-        match tag_id {
-            0x01 => Ok(Tag::Byte(fr.read()?)),
-            0x02 => Ok(Tag::Short(fr.read()?)),
-            0x03 => Ok(Tag::Int(fr.read()?)),
-            0x04 => Ok(Tag::Long(fr.read()?)),
-            0x05 => Ok(Tag::Float(fr.read()?)),
-            0x06 => Ok(Tag::Double(fr.read()?)),
-            0x07 => Ok(Tag::ByteArray(read_array!(fr))),
-            0x08 => Ok(Tag::String(fr.read()?)),
-            0x09 => Ok(Tag::List(read_list(fr.read()?, fr)?)),
-            0x0A => Ok(Tag::Compound(read_compound(fr)?)),
-            0x0B => Ok(Tag::IntArray(read_array!(fr))),
-            0x0C => Ok(Tag::LongArray(read_array!(fr))),
-            x => Err(BinError::Parsing(format!("Invalid Tag ID: {}", x))),
-        }
+        read_tag(tag_id, fr, 0)
     }
 }
 
 macro_rules! write_array {
     ($v:expr, $fw:expr) => {{
+        $fw.reserve(4 + std::mem::size_of_val(&$v[..]));
         $fw.write(&($v.len() as i32));
-        for i in 0..$v.len() {
-            $fw.write(&$v[i]);
-        }
+        write_numbers(&$v[..], $fw);
     }};
 }
 
 macro_rules! write_list {
-    ($id:literal, $v:expr, $fw:expr) => {{
-        $fw.write(&$id);
+    ($id:expr, $v:expr, $fw:expr) => {{
+        $fw.write::<u8>(&$id);
         write_array!($v, $fw);
     }};
 }
 
+macro_rules! write_tag_list {
+    ($id:expr, $v:expr, $fw:expr) => {{
+        $fw.write::<u8>(&$id);
+        $fw.write(&($v.len() as i32));
+        $fw.write_all(&$v[..]);
+    }};
+}
+
 macro_rules! write_array_list {
-    ($id:literal, $v:expr, $fw:expr) => {{
-        $fw.write(&$id);
+    ($id:expr, $v:expr, $fw:expr) => {{
+        $fw.write::<u8>(&$id);
         $fw.write(&($v.len() as i32));
-        for i in 0..$v.len() {
-            let w = &$v[i];
+        for w in $v.iter() {
             $fw.write(&(w.len() as i32));
-            for j in 0..w.len() {
-                $fw.write(&w[j]);
-            }
+            write_numbers(&w[..], $fw);
         }
     }};
 }
 
 impl Writer for Tag {
+    ///writes the payload, compounds closed with their Tag End like [`Tag::read`] requires
+    fn write(&self, fw: &mut impl FileWriter) {
+        write_tag(self, fw)
+    }
+}
+
+impl Tag {
+    ///writes only the payload, exactly what [`Tag::read`] expects given the tag's id,
+    ///the same as [`Writer::write`]
+    pub fn write_payload(&self, fw: &mut impl FileWriter) {
+        write_tag(self, fw)
+    }
+}
+
+///the NBT encodings used by the different editions and transports
+///
+///| flavor           | endianness | root name | string/list/array lengths, ints, longs | tag ids        |
+///|------------------|------------|-----------|----------------------------------------|----------------|
+///| `JavaDisk`       | big        | yes       | `u16` / `i32` / `i32` / `i64`          | `0x00..=0x0C`  |
+///| `JavaNetwork`    | big        | no        | `u16` / `i32` / `i32` / `i64`          | `0x00..=0x0C`  |
+///| `BedrockDisk`    | little     | yes       | `u16` / `i32` / `i32` / `i64`          | `0x00..=0x0B`  |
+///| `BedrockNetwork` | little     | yes       | varints                                | `0x00..=0x0B`  |
+///
+///Java network NBT (1.20.2+) drops the root's name but keeps its tag id,
+///Bedrock network NBT writes string lengths as unsigned varints and everything else in the
+///column as zigzag varints, see [`IntEncoding`]
+///
+///Bedrock has no `TAG_Long_Array`, [`Tag::read_with`] and [`Tag::write_with`] error on long arrays
+///(lists of them included) rather than producing a file Bedrock can't load
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NbtFlavor {
+    JavaDisk,
+    JavaNetwork,
+    BedrockDisk,
+    BedrockNetwork,
+}
+
+impl NbtFlavor {
+    pub fn is_little_endian(&self) -> bool {
+        matches!(self, NbtFlavor::BedrockDisk | NbtFlavor::BedrockNetwork)
+    }
+
+    ///whether the root tag id is followed by a name
+    pub fn has_root_name(&self) -> bool {
+        !matches!(self, NbtFlavor::JavaNetwork)
+    }
+
+    ///whether the flavor knows the tag id
+    pub fn supports_tag_id(&self, tag_id: u8) -> bool {
+        match self {
+            NbtFlavor::JavaDisk | NbtFlavor::JavaNetwork => tag_id <= TAG_LONG_ARRAY,
+            NbtFlavor::BedrockDisk | NbtFlavor::BedrockNetwork => tag_id <= TAG_INT_ARRAY,
+        }
+    }
+
+    ///errors on the first tag of the tree the flavor doesn't know
+    fn check_tag(&self, tag: TagRef) -> BinResult<()> {
+        let tag_id = match tag {
+            TagRef::List(list) => list.element_id(),
+            tag => tag.tag_id(),
+        };
+        if !self.supports_tag_id(tag_id) {
+            return Err(BinError::UnsupportedTag {
+                tag_id,
+                flavor: *self,
+                pos: None,
+            });
+        }
+        match tag {
+            TagRef::List(list) => list.iter().try_for_each(|element| self.check_tag(element)),
+            TagRef::Compound(map) => map.values().try_for_each(|v| self.check_tag(v.as_ref())),
+            _ => Ok(()),
+        }
+    }
+
+    ///how ints, longs and string, list and array lengths are encoded
+    pub fn int_encoding(&self) -> IntEncoding {
+        match self {
+            NbtFlavor::BedrockNetwork => IntEncoding::VarInt,
+            _ => IntEncoding::Fixed,
+        }
+    }
+
+    fn read_root(&self, fr: &mut impl FileReader) -> BinResult<(String, Tag)> {
+        if self.has_root_name() {
+            fr.read::<NamedRoot>().map(|r| (r.0, r.1))
+        } else {
+            fr.read::<UnnamedRoot>().map(|r| (String::new(), r.0))
+        }
+    }
+}
+
+impl Tag {
+    ///reads a payload like [`Tag::read`] and also returns the exact bytes it was parsed from
+    ///
+    ///writing those bytes back keeps the subtree's original encoding, compound key order included
+    pub fn read_raw(tag_id: u8, fr: &mut impl FileReader) -> BinResult<(Tag, Vec<u8>)> {
+        let start = fr.position();
+        let tag = Tag::read(tag_id, fr)?;
+        Ok((tag, fr.buffer()[start..fr.position()].to_vec()))
+    }
+
+    ///reads a payload like [`Tag::read`] and also returns how many bytes it took up,
+    ///for formats embedding NBT among other fields
+    pub fn read_counted(tag_id: u8, fr: &mut impl FileReader) -> BinResult<(Tag, usize)> {
+        let start = fr.position();
+        let tag = Tag::read(tag_id, fr)?;
+        Ok((tag, fr.position() - start))
+    }
+
+    ///reads a root tag the way `flavor` frames it, the name is empty for unnamed roots
+    ///
+    ///the reader's own endianness is ignored in favour of the flavor's, tags the flavor
+    ///doesn't have are rejected before their payload is read
+    pub fn read_with(flavor: NbtFlavor, fr: &mut impl FileReader) -> BinResult<(String, Tag)> {
+        let options = ReadOptions {
+            ints: flavor.int_encoding(),
+            flavor: Some(flavor),
+            ..*fr.options()
+        };
+        let start = fr.position();
+        let (root, end) = if flavor.is_little_endian() {
+            let mut flavored = FileReaderLE::new(fr.buffer(), start).with_options(options);
+            (flavor.read_root(&mut flavored), flavored.position())
+        } else {
+            let mut flavored = FileReaderBE::new(fr.buffer(), start).with_options(options);
+            (flavor.read_root(&mut flavored), flavored.position())
+        };
+        let (name, tag) = root?;
+        fr.get_slice(end - start)?;
+        Ok((name, tag))
+    }
+
+    ///reads named roots back to back until the end of the reader, for append-only NBT streams
+    ///
+    ///errors come wrapped with the index and offset of the document that failed
+    pub fn read_all(fr: &mut impl FileReader) -> BinResult<Vec<(String, Tag)>> {
+        let mut documents = Vec::new();
+        while !fr.at_end() {
+            let start = fr.position();
+            let document = read_named(fr).map_err(|e| {
+                e.context(format!("Document {} at byte {}", documents.len(), start))
+            })?;
+            documents.push(document);
+        }
+        Ok(documents)
+    }
+
+    ///writes the tag as a root the way `flavor` frames it, `name` is dropped for unnamed roots
+    ///
+    ///the writer's own endianness is ignored in favour of the flavor's
+    pub fn write_with(
+        &self,
+        flavor: NbtFlavor,
+        name: &str,
+        fw: &mut impl FileWriter,
+    ) -> BinResult<()> {
+        flavor.check_tag(self.as_ref())?;
+        let root = RootRef {
+            name: flavor.has_root_name().then_some(name),
+            tag: self,
+        };
+        let endianness = if flavor.is_little_endian() {
+            Endianness::Little
+        } else {
+            Endianness::Big
+        };
+        let options = WriteOptions {
+            ints: flavor.int_encoding(),
+        };
+        root.write(&mut ReframedWriter::new(fw, endianness, options));
+        Ok(())
+    }
+}
+
+///a root tag with its name, read through [`Io`] so it can be read in either endianness
+struct NamedRoot(String, Tag);
+
+struct UnnamedRoot(Tag);
+
+struct RootRef<'a> {
+    name: Option<&'a str>,
+    tag: &'a Tag,
+}
+
+impl Writer for RootRef<'_> {
+    fn write(&self, fw: &mut impl FileWriter) {
+        fw.write(&self.tag.tag_id());
+        if let Some(name) = self.name {
+            fw.write_raw_string_bytes(&encode_mutf8(name));
+        }
+        write_tag(self.tag, fw);
+    }
+}
+
+impl Io for NamedRoot {
+    fn read(fr: &mut impl FileReader) -> BinResult<Self> {
+        let (name, tag) = read_named(fr)?;
+        Ok(NamedRoot(name, tag))
+    }
+}
+
+impl Writer for NamedRoot {
+    fn write(&self, fw: &mut impl FileWriter) {
+        RootRef {
+            name: Some(&self.0),
+            tag: &self.1,
+        }
+        .write(fw)
+    }
+}
+
+impl Io for UnnamedRoot {
+    fn read(fr: &mut impl FileReader) -> BinResult<Self> {
+        let tag_id: u8 = fr.read()?;
+        Ok(UnnamedRoot(Tag::read(tag_id, fr)?))
+    }
+}
+
+impl Writer for UnnamedRoot {
     fn write(&self, fw: &mut impl FileWriter) {
-        write_tag(self, fw, false)
+        RootRef {
+            name: None,
+            tag: &self.0,
+        }
+        .write(fw)
+    }
+}
+
+///reads the tag id, the name and the payload, the framing of a file's root tag
+pub fn read_named(fr: &mut impl FileReader) -> BinResult<(String, Tag)> {
+    let tag_id: u8 = fr.read()?;
+    let name = fr.read()?;
+    Ok((name, Tag::read(tag_id, fr)?))
+}
+
+impl Tag {
+    ///compatibility shim for malformed files of old tools that wrote a root's name before its
+    ///tag id, reading the name, the tag id and then a regular payload
+    ///
+    ///only the root is swapped, compound entries inside it are read the standard way,
+    ///never use this for files that follow the format, see [`read_named`]
+    pub fn read_legacy_name_first(fr: &mut impl FileReader) -> BinResult<(String, Tag)> {
+        let name = fr.read()?;
+        let tag_id: u8 = fr.read()?;
+        Ok((name, Tag::read(tag_id, fr)?))
+    }
+}
+
+///reads an uncompressed named root in ``big endian``, the Java encoding, bytes after it are ignored
+pub fn read_be(bytes: &[u8]) -> BinResult<(String, Tag)> {
+    read_named(&mut FileReaderBE::new(bytes, 0))
+}
+
+///reads an uncompressed named root in ``little endian``, the Bedrock encoding, bytes after it are ignored
+pub fn read_le(bytes: &[u8]) -> BinResult<(String, Tag)> {
+    read_named(&mut FileReaderLE::new(bytes, 0))
+}
+
+///writes the tag as an uncompressed named root in ``big endian``, the counterpart of [`read_be`]
+pub fn write_be(name: &str, tag: &Tag) -> Vec<u8> {
+    let mut fw = FileWriterBE::new();
+    fw.write_named_tag(name, tag);
+    fw.bytes()
+}
+
+///writes the tag as an uncompressed named root in ``little endian``, the counterpart of [`read_le`]
+pub fn write_le(name: &str, tag: &Tag) -> Vec<u8> {
+    let mut fw = FileWriterLE::new();
+    fw.write_named_tag(name, tag);
+    fw.bytes()
+}
+
+///writes the tag as a named root in ``big endian`` compressed with `compression` into `inner`,
+///serializing and compressing as it goes rather than building the whole document first
+///
+///the output is the same as `compression.encode(write_be(name, tag))`, `inner` is returned once
+///the compressed stream is finished
+pub fn write_compressed<W: std::io::Write>(
+    name: &str,
+    tag: &Tag,
+    compression: &Compression,
+    inner: W,
+) -> BinResult<W> {
+    let mut fw = StreamWriter::new(compression.writer(inner), Endianness::Big);
+    fw.write_named_tag(name, tag);
+    Ok(fw.finish()?.finish()?)
+}
+
+///reads a Bedrock `level.dat`, returning its storage version and its root
+///
+///the NBT is preceded by a header of two ``little endian`` `u32`s: the version
+///and the length of the NBT, which has to match the bytes following the header
+pub fn read_bedrock_level(bytes: &[u8]) -> BinResult<(u32, Tag)> {
+    let mut fr = FileReaderLE::new(bytes, 0);
+    let version: u32 = fr.read()?;
+    let len: u32 = fr.read()?;
+    if len as usize != bytes.len() - 8 {
+        return Err(BinError::LevelLengthMismatch {
+            declared: len,
+            found: bytes.len() - 8,
+        });
+    }
+    Ok((version, Tag::read_with(NbtFlavor::BedrockDisk, &mut fr)?.1))
+}
+
+///writes a Bedrock `level.dat` with its version and length header, the counterpart of
+///[`read_bedrock_level`], the root is written unnamed like the game does
+pub fn write_bedrock_level(version: u32, tag: &Tag) -> BinResult<Vec<u8>> {
+    let mut fw = FileWriterLE::new();
+    fw.write(&version);
+    fw.write(&0u32);
+    tag.write_with(NbtFlavor::BedrockDisk, "", &mut fw)?;
+    let mut bytes = fw.bytes();
+    let len = (bytes.len() - 8) as u32;
+    bytes[4..8].copy_from_slice(&len.to_le_bytes());
+    Ok(bytes)
+}
+
+///first data version (20w17a) whose block state entries don't span two longs
+pub const PADDED_BLOCK_STATES_VERSION: i32 = 2529;
+const BLOCKS_PER_SECTION: usize = 4096;
+
+///unpacks the 4096 palette indices of a chunk section, in `y, z, x` order
+///
+///takes either the 1.18+ layout (a `block_states` compound holding `palette` and `data`) or
+///the older one (`Palette` and `BlockStates` in the section itself), entries are padded to
+///whole longs since [`PADDED_BLOCK_STATES_VERSION`] and packed across longs before it
+pub fn decode_block_states(section: &Tag, data_version: i32) -> BinResult<Vec<u32>> {
+    let (palette, data) = match (section.get_optional_compound("block_states")?, section) {
+        (Some(states), _) => (states.get("palette"), states.get("data")),
+        (None, Tag::Compound(map)) => (map.get("Palette"), map.get("BlockStates")),
+        (None, _) => (None, None),
+    };
+    let palette_len = match palette {
+        Some(Tag::List(palette)) if !palette.is_empty() => palette.len(),
+        _ => return Err(BinError::MissingBlockPalette),
+    };
+    let data = match data {
+        Some(Tag::LongArray(data)) => &data[..],
+        None if palette_len == 1 => return Ok(vec![0; BLOCKS_PER_SECTION]),
+        _ => return Err(BinError::MissingBlockStates),
+    };
+
+    let bits = ((usize::BITS - (palette_len - 1).leading_zeros()) as usize).max(4);
+    let padded = data_version >= PADDED_BLOCK_STATES_VERSION;
+    let per_long = 64 / bits;
+    let expected = if padded {
+        BLOCKS_PER_SECTION.div_ceil(per_long)
+    } else {
+        (BLOCKS_PER_SECTION * bits).div_ceil(64)
+    };
+    if data.len() != expected {
+        return Err(BinError::BlockStatesLength {
+            len: data.len(),
+            bits,
+            expected,
+        });
+    }
+
+    let mask = (1u64 << bits) - 1;
+    let mut states = Vec::with_capacity(BLOCKS_PER_SECTION);
+    for i in 0..BLOCKS_PER_SECTION {
+        let v = if padded {
+            data[i / per_long] as u64 >> (i % per_long * bits)
+        } else {
+            let (long, offset) = (i * bits / 64, i * bits % 64);
+            let mut v = data[long] as u64 >> offset;
+            if offset + bits > 64 {
+                v |= (data[long + 1] as u64) << (64 - offset);
+            }
+            v
+        } & mask;
+        if v as usize >= palette_len {
+            return Err(BinError::BlockStateOutOfPalette {
+                block: i,
+                entry: v,
+                palette_len,
+            });
+        }
+        states.push(v as u32);
+    }
+    Ok(states)
+}
+
+///reads every `<uuid>.dat` file of a `playerdata` directory, detecting their compression
+///
+///files that aren't named after a uuid are skipped, each file is read as the iterator advances
+pub fn read_playerdata_dir<P: AsRef<Path>>(
+    dir: P,
+) -> BinResult<impl Iterator<Item = (u128, BinResult<Tag>)>> {
+    let entries = std::fs::read_dir(dir)?;
+    Ok(entries.filter_map(|entry| {
+        let path = entry.ok()?.path();
+        if path.extension()? != "dat" {
+            return None;
+        }
+        let uuid = parse_uuid(path.file_stem()?.to_str()?)?;
+        Some((uuid, read_playerdata(&path)))
+    }))
+}
+
+fn read_playerdata(path: &Path) -> BinResult<Tag> {
+    Tag::try_from(std::fs::read(path)?)
+}
+
+///parses a whole ``Java`` NBT document like `level.dat`, detecting its compression
+///
+///returns the root's name along with the root tag
+pub fn read_document(bytes: &[u8]) -> BinResult<(String, Tag)> {
+    match Compression::detect(bytes) {
+        Some(Compression::Uncompressed) => read_named(&mut FileReaderBE::new(bytes, 0)),
+        Some(compression) => {
+            read_named(&mut FileReaderBE::new(&compression.decode_slice(bytes)?, 0))
+        }
+        None => Err(undetected_compression(bytes)),
+    }
+}
+
+fn undetected_compression(bytes: &[u8]) -> BinError {
+    match bytes.first() {
+        None => BinError::EmptyInput,
+        Some(&b) => BinError::UnrecognizedCompression(b),
     }
 }
 
-fn write_tag(tag: &Tag, fw: &mut impl FileWriter, end: bool) {
+///parses a whole document with [`read_document`], dropping the root's name
+impl TryFrom<&[u8]> for Tag {
+    type Error = BinError;
+
+    fn try_from(bytes: &[u8]) -> BinResult<Self> {
+        Ok(read_document(bytes)?.1)
+    }
+}
+
+impl TryFrom<Vec<u8>> for Tag {
+    type Error = BinError;
+
+    fn try_from(bytes: Vec<u8>) -> BinResult<Self> {
+        Tag::try_from(bytes.as_slice())
+    }
+}
+
+///parses a hyphenated uuid like `069a79f4-44e9-4726-a5be-fca90e38aaf5`
+fn parse_uuid(s: &str) -> Option<u128> {
+    let groups: Vec<&str> = s.split('-').collect();
+    if groups.iter().map(|g| g.len()).collect::<Vec<_>>() != [8, 4, 4, 4, 12] {
+        return None;
+    }
+    let hex = groups.concat();
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u128::from_str_radix(&hex, 16).ok()
+}
+
+///writes the tag id, the name and the payload, the framing of a file's root tag
+pub(crate) fn write_named(name: &str, tag: &Tag, fw: &mut impl FileWriter) {
+    RootRef {
+        name: Some(name),
+        tag,
+    }
+    .write(fw)
+}
+
+fn write_tag(tag: &Tag, fw: &mut impl FileWriter) {
     match tag {
         Tag::Byte(v) => fw.write(v),
         Tag::Short(v) => fw.write(v),
@@ -240,49 +2199,165 @@ fn write_tag(tag: &Tag, fw: &mut impl FileWriter, end: bool) {
         Tag::ByteArray(v) => write_array!(v, fw),
         Tag::String(v) => fw.write(v),
         Tag::List(v) => fw.write(v),
-        Tag::Compound(map) => {
-            for (k, v) in map.iter() {
-                fw.write(&v.tag_id());
-                fw.write(k);
-                write_tag(v, fw, true);
-            }
-            if end {
-                fw.write::<u8>(&0x00);
-            }
-        }
+        Tag::Compound(map) => write_compound(map, fw),
         Tag::IntArray(v) => write_array!(v, fw),
         Tag::LongArray(v) => write_array!(v, fw),
     }
 }
 
+///writes the entries and the closing Tag End, in [`canonical_key_order`] with `sorted_compounds`
+fn write_compound(map: &Compound, fw: &mut impl FileWriter) {
+    #[cfg(not(feature = "sorted_compounds"))]
+    let entries = map.iter();
+    #[cfg(feature = "sorted_compounds")]
+    let entries = {
+        //the map is already in `str` order, which only differs for a few characters
+        let mut entries: Vec<_> = map.iter().collect();
+        entries.sort_by(|a, b| canonical_key_order(a.0, b.0));
+        entries
+    };
+    for (k, v) in entries {
+        fw.write(&v.tag_id());
+        fw.write(k);
+        write_tag(v, fw);
+    }
+    fw.write::<u8>(&TAG_END);
+}
+
 impl Writer for List {
+    ///empty lists are written as lists of `TAG_End` whatever their variant, like vanilla does
     fn write(&self, fw: &mut impl FileWriter) {
         match self {
-            List::Empty => fw.append(&mut vec![0x00; 5]),
-            List::Byte(arr) => write_list!(0x01, arr, fw),
-            List::Short(arr) => write_list!(0x02, arr, fw),
-            List::Int(arr) => write_list!(0x03, arr, fw),
-            List::Long(arr) => write_list!(0x04, arr, fw),
-            List::Float(arr) => write_list!(0x05, arr, fw),
-            List::Double(arr) => write_list!(0x06, arr, fw),
-            List::ByteArray(arr) => write_array_list!(0x07, arr, fw),
-            List::String(arr) => write_list!(0x08, arr, fw),
-            List::List(arr) => write_list!(0x09, arr, fw),
+            List::Empty => fw.extend(&[0x00; 5]),
+            list if list.is_empty() => List::Empty.write(fw),
+            List::Byte(arr) => write_list!(TAG_BYTE, arr, fw),
+            List::Short(arr) => write_list!(TAG_SHORT, arr, fw),
+            List::Int(arr) => write_list!(TAG_INT, arr, fw),
+            List::Long(arr) => write_list!(TAG_LONG, arr, fw),
+            List::Float(arr) => write_list!(TAG_FLOAT, arr, fw),
+            List::Double(arr) => write_list!(TAG_DOUBLE, arr, fw),
+            List::ByteArray(arr) => write_array_list!(TAG_BYTE_ARRAY, arr, fw),
+            List::String(arr) => write_tag_list!(TAG_STRING, arr, fw),
+            List::List(arr) => write_tag_list!(TAG_LIST, arr, fw),
             List::Compound(arr) => {
-                fw.write::<u8>(&0x0A);
+                fw.write::<u8>(&TAG_COMPOUND);
                 fw.write(&(arr.len() as i32));
-                for i in 0..arr.len() {
-                    let map = &arr[i];
-                    for (k, v) in map.iter() {
-                        fw.write(&v.tag_id());
-                        fw.write(k);
-                        write_tag(v, fw, true);
+                for map in arr {
+                    write_compound(map, fw);
+                }
+            }
+            List::IntArray(arr) => write_array_list!(TAG_INT_ARRAY, arr, fw),
+            List::LongArray(arr) => write_array_list!(TAG_LONG_ARRAY, arr, fw),
+        }
+    }
+}
+
+const PATCH_SET: u8 = 0x00;
+const PATCH_REMOVE: u8 = 0x01;
+
+///encodes the changes turning `old` into `new` as a compact binary patch for [`apply_binary`]
+///
+///compounds are diffed key by key, any other changed value (lists included) is replaced whole,
+///each operation is addressed by the path of compound keys leading to it
+pub fn diff_binary(old: &Tag, new: &Tag) -> Vec<u8> {
+    let mut ops = FileWriterBE::new();
+    let mut count = 0;
+    diff_tags(old, new, &mut Vec::new(), &mut ops, &mut count);
+    let mut fw = FileWriterBE::new();
+    fw.write(&count);
+    fw.append(&mut ops.bytes());
+    fw.bytes()
+}
+
+fn diff_tags<'a>(
+    old: &'a Tag,
+    new: &'a Tag,
+    path: &mut Vec<&'a String>,
+    fw: &mut FileWriterBE,
+    count: &mut i32,
+) {
+    match (old, new) {
+        (Tag::Compound(old), Tag::Compound(new)) => {
+            for k in old.keys().filter(|k| !new.contains_key(*k)) {
+                path.push(k);
+                write_patch_op(PATCH_REMOVE, path, None, fw);
+                path.pop();
+                *count += 1;
+            }
+            for (k, v) in new.iter() {
+                path.push(k);
+                match old.get(k) {
+                    Some(old) => diff_tags(old, v, path, fw, count),
+                    None => {
+                        write_patch_op(PATCH_SET, path, Some(v), fw);
+                        *count += 1;
                     }
-                    fw.write::<u8>(&0x00);
                 }
+                path.pop();
+            }
+        }
+        (old, new) if old != new => {
+            write_patch_op(PATCH_SET, path, Some(new), fw);
+            *count += 1;
+        }
+        _ => {}
+    }
+}
+
+fn write_patch_op(op: u8, path: &[&String], tag: Option<&Tag>, fw: &mut FileWriterBE) {
+    fw.write(&op);
+    fw.write(&(path.len() as u16));
+    path.iter().for_each(|k| fw.write(*k));
+    if let Some(tag) = tag {
+        fw.write(&tag.tag_id());
+        tag.write_payload(fw);
+    }
+}
+
+///applies a patch made by [`diff_binary`], so `apply_binary(old, &diff_binary(old, new))` equals `new`
+pub fn apply_binary(old: &Tag, patch: &[u8]) -> BinResult<Tag> {
+    let mut fr = FileReaderBE::new(patch, 0);
+    let mut tag = old.clone();
+    let count: i32 = fr.read()?;
+    for _ in 0..count {
+        let op: u8 = fr.read()?;
+        let len: u16 = fr.read()?;
+        let mut path = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            path.push(fr.read::<String>()?);
+        }
+        let mut parent = &mut tag;
+        if let Some((last, keys)) = path.split_last() {
+            for k in keys {
+                parent = match parent {
+                    Tag::Compound(map) => map.get_mut(k),
+                    _ => None,
+                }
+                .ok_or_else(|| missing_patch_path(&path))?;
+            }
+            let Tag::Compound(map) = parent else {
+                return Err(missing_patch_path(&path));
+            };
+            match op {
+                PATCH_SET => {
+                    let tag_id = fr.read()?;
+                    map.insert(last.clone(), Tag::read(tag_id, &mut fr)?);
+                }
+                PATCH_REMOVE => {
+                    map.remove(last).ok_or_else(|| missing_patch_path(&path))?;
+                }
+                x => return Err(BinError::InvalidPatchOp(x)),
             }
-            List::IntArray(arr) => write_array_list!(0x0B, arr, fw),
-            List::LongArray(arr) => write_array_list!(0x0B, arr, fw),
+        } else if op == PATCH_SET {
+            let tag_id = fr.read()?;
+            tag = Tag::read(tag_id, &mut fr)?;
+        } else {
+            return Err(BinError::PatchRemovesRoot);
         }
     }
+    Ok(tag)
+}
+
+fn missing_patch_path(path: &[String]) -> BinError {
+    BinError::MissingPatchPath(path.to_vec())
 }
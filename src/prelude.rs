@@ -0,0 +1,11 @@
+//! `use mm_io::prelude::*;` brings the reader/writer traits and the common types into scope
+
+pub use crate::binary::{
+    BinError, BinResult, Endianness, FileReader, FileReaderBE, FileReaderLE, FileWriter,
+    FileWriterBE, FileWriterLE, GrowableReader, IntEncoding, Io, Limits, ReadOptions,
+    ReservePolicy, SliceWriter, StreamWriter, TagIo, WriteOptions, Writer,
+};
+pub use crate::compression::Compression;
+pub use crate::convert::{ReadFrom, WriteTo};
+pub use crate::nbt::{Compound, HashableTag, List, ListElement, NbtFlavor, Number, Tag, TagRef};
+pub use crate::region::{RegionFile, RegionHeader};
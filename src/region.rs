@@ -0,0 +1,398 @@
+use crate::binary::{BinError, BinResult, FileReader, FileReaderBE, FileWriter, FileWriterBE};
+use crate::compression::Compression;
+pub use crate::compression::EXTERNAL_CHUNK_FLAG;
+use crate::nbt::{self, Tag};
+use std::borrow::Borrow;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SECTOR_SIZE: usize = 4096;
+pub const HEADER_SIZE: usize = SECTOR_SIZE * 2;
+pub const CHUNKS_PER_REGION: usize = 1024;
+
+///a chunk's coordinates inside its region and its root, or why it couldn't be read
+pub type ChunkResult = BinResult<(u8, u8, Tag)>;
+///a chunk of [`read_world_region_dir`]: region x and z, world chunk x and z, and the chunk
+pub type WorldChunk = (i32, i32, i32, i32, BinResult<Tag>);
+
+///an Anvil (`.mca`) region file held in memory
+///
+///the first sector holds the chunk locations (3 byte sector offset + 1 byte sector count),
+///the second sector holds the last-modified unix timestamp of every chunk
+pub struct RegionFile {
+    bytes: Vec<u8>,
+    path: Option<PathBuf>,
+}
+
+impl RegionFile {
+    ///creates an empty region with a zeroed header
+    pub fn new() -> Self {
+        Self {
+            bytes: vec![0x00; HEADER_SIZE],
+            path: None,
+        }
+    }
+
+    ///reads a region file from disk, its path is kept to locate external `.mcc` chunks
+    pub fn open<P: AsRef<Path>>(path: P) -> BinResult<Self> {
+        let mut region = Self::from_bytes(std::fs::read(&path)?)?;
+        region.path = Some(path.as_ref().to_path_buf());
+        Ok(region)
+    }
+
+    ///a zero-length file, like one that was just created, is an empty region,
+    ///any other file too short for the header is an error
+    pub fn from_bytes(bytes: Vec<u8>) -> BinResult<Self> {
+        if bytes.is_empty() {
+            return Ok(Self::new());
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(BinError::RegionHeaderTooShort {
+                len: bytes.len(),
+                needed: HEADER_SIZE,
+            });
+        }
+        Ok(Self { bytes, path: None })
+    }
+
+    ///returns the location entry of the chunk as `(sector offset, sector count)`
+    pub fn location(&self, x: u8, z: u8) -> Option<(u32, u8)> {
+        let i = chunk_index(x, z)?;
+        let mut fr = FileReaderBE::new(&self.bytes, i * 4);
+        Some((fr.read_u24_be().ok()?, fr.read().ok()?))
+    }
+
+    ///coordinates of every populated chunk slot, in header order (`x` varies fastest)
+    pub fn populated_chunks(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        (0..CHUNKS_PER_REGION)
+            .map(|i| ((i % 32) as u8, (i / 32) as u8))
+            .filter(|&(x, z)| self.location(x, z).is_some_and(is_populated))
+    }
+
+    ///parses the location and timestamp tables without touching any chunk data
+    pub fn header(&self) -> RegionHeader {
+        RegionHeader::from_bytes(&self.bytes).expect("regions always hold a full header")
+    }
+
+    ///returns the last-modified unix timestamp of the chunk, `None` if the slot is empty
+    pub fn timestamp(&self, x: u8, z: u8) -> Option<u32> {
+        let i = chunk_index(x, z)?;
+        if !is_populated(self.location(x, z)?) {
+            return None;
+        }
+        FileReaderBE::new(&self.bytes, SECTOR_SIZE + i * 4)
+            .read()
+            .ok()
+    }
+
+    pub fn set_timestamp(&mut self, x: u8, z: u8, timestamp: u32) -> BinResult<()> {
+        let i = chunk_index(x, z).ok_or_else(|| out_of_region(x, z))?;
+        let pos = SECTOR_SIZE + i * 4;
+        self.bytes[pos..pos + 4].copy_from_slice(&timestamp.to_be_bytes());
+        Ok(())
+    }
+
+    ///reads and decompresses the chunk, `None` if the slot is empty
+    ///
+    ///chunks flagged as external are loaded from the `c.X.Z.mcc` file next to the region file
+    pub fn read_chunk(&self, x: u8, z: u8) -> BinResult<Option<Tag>> {
+        let location = self.location(x, z).ok_or_else(|| out_of_region(x, z))?;
+        if !is_populated(location) {
+            return Ok(None);
+        }
+        let offset = location.0;
+        let mut fr = FileReaderBE::new(&self.bytes, offset as usize * SECTOR_SIZE);
+        let len: u32 = fr.read()?;
+        let (compression, external) = Compression::from_scheme_byte(fr.read()?)?;
+        let data = if external {
+            compression.decode(self.read_external_chunk(x, z)?)?
+        } else {
+            let start = fr.position();
+            fr.get_slice((len as usize).saturating_sub(1))?;
+            compression.decode_range(&self.bytes, start..fr.position())?
+        };
+        Ok(Some(nbt::read_named(&mut FileReaderBE::new(&data, 0))?.1))
+    }
+
+    ///reads the populated chunks one at a time as the iterator advances, in header order
+    ///
+    ///a chunk that fails to read yields its error, noting the chunk, and the iteration goes on
+    pub fn iter_chunks(&self) -> impl Iterator<Item = ChunkResult> + '_ {
+        chunks_of(self).map(|(x, z, chunk)| chunk.map(|tag| (x, z, tag)))
+    }
+
+    ///returns the chunk's compression and its still compressed data, `None` if the slot is empty
+    ///
+    ///lets chunks be copied between regions with [`RegionFile::write_chunk_raw`] without
+    ///decompressing them, external chunks are read from their `c.X.Z.mcc` file
+    pub fn read_chunk_raw(&self, x: u8, z: u8) -> BinResult<Option<(Compression, Vec<u8>)>> {
+        let location = self.location(x, z).ok_or_else(|| out_of_region(x, z))?;
+        if !is_populated(location) {
+            return Ok(None);
+        }
+        let offset = location.0;
+        let mut fr = FileReaderBE::new(&self.bytes, offset as usize * SECTOR_SIZE);
+        let len: u32 = fr.read()?;
+        let (compression, external) = Compression::from_scheme_byte(fr.read()?)?;
+        let data = if external {
+            self.read_external_chunk(x, z)?
+        } else {
+            fr.get_slice((len as usize).saturating_sub(1))?.to_vec()
+        };
+        Ok(Some((compression, data)))
+    }
+
+    ///compresses and stores the chunk, updating its location and timestamp
+    ///
+    ///the chunk is rewritten in place if it still fits its sectors, otherwise it's appended
+    ///to the end of the file, chunks needing more than 255 sectors aren't supported
+    pub fn write_chunk(
+        &mut self,
+        x: u8,
+        z: u8,
+        tag: &Tag,
+        compression: Compression,
+    ) -> BinResult<()> {
+        let mut fw = FileWriterBE::new();
+        fw.write_named_tag("", tag);
+        let data = fw.into_compressed(compression)?;
+        self.write_chunk_raw(x, z, compression, data)
+    }
+
+    ///stores already compressed chunk data as is, like the data of [`RegionFile::read_chunk_raw`]
+    ///
+    ///`data` isn't checked to decompress with `compression`, it's always stored in the region
+    ///even if it came from an external chunk
+    pub fn write_chunk_raw(
+        &mut self,
+        x: u8,
+        z: u8,
+        compression: Compression,
+        mut data: Vec<u8>,
+    ) -> BinResult<()> {
+        let (offset, sector_count) = self.location(x, z).ok_or_else(|| out_of_region(x, z))?;
+        let mut fw = FileWriterBE::new();
+        fw.write(&(data.len() as u32 + 1));
+        fw.write(&compression.to_scheme_byte(false));
+        fw.append(&mut data);
+        fw.pad_to(SECTOR_SIZE);
+        let chunk = fw.bytes();
+        let sectors = chunk.len() / SECTOR_SIZE;
+        if sectors > u8::MAX as usize {
+            return Err(BinError::ChunkTooLarge { x, z, sectors });
+        }
+
+        let offset = if offset != 0 && sectors <= sector_count as usize {
+            offset as usize
+        } else {
+            self.bytes
+                .resize(self.bytes.len().div_ceil(SECTOR_SIZE) * SECTOR_SIZE, 0x00);
+            let end = self.bytes.len() / SECTOR_SIZE;
+            self.bytes.resize(self.bytes.len() + chunk.len(), 0x00);
+            end
+        };
+        let pos = offset * SECTOR_SIZE;
+        self.bytes[pos..pos + chunk.len()].copy_from_slice(&chunk);
+
+        let i = x as usize + z as usize * 32;
+        let mut fw = FileWriterBE::new();
+        fw.write_u24_be(offset as u32);
+        fw.write(&(sectors as u8));
+        self.bytes[i * 4..i * 4 + 4].copy_from_slice(&fw.bytes());
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as u32);
+        self.set_timestamp(x, z, now)
+    }
+
+    ///reads the still compressed data of an external chunk, errors name the file
+    fn read_external_chunk(&self, x: u8, z: u8) -> BinResult<Vec<u8>> {
+        let path = self.external_chunk_path(x, z)?;
+        std::fs::read(&path)
+            .map_err(|e| BinError::from(e).context(format!("Reading {}", path.display())))
+    }
+
+    ///path of the `c.X.Z.mcc` file holding an oversized chunk of this region
+    fn external_chunk_path(&self, x: u8, z: u8) -> BinResult<PathBuf> {
+        let path = self.path.as_ref().ok_or(BinError::NoRegionPath { x, z })?;
+        let (region_x, region_z) = region_coordinates(path)
+            .ok_or_else(|| BinError::InvalidRegionFileName(path.clone()))?;
+        let name = format!(
+            "c.{}.{}.mcc",
+            region_x * 32 + x as i32,
+            region_z * 32 + z as i32
+        );
+        let external = path.with_file_name(name);
+        if !external.exists() {
+            return Err(BinError::MissingExternalChunk { path: external });
+        }
+        Ok(external)
+    }
+
+    pub fn bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl Default for RegionFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///location and timestamp of a chunk slot, as stored in the region header
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct ChunkSlot {
+    ///in sectors from the start of the file
+    pub offset: u32,
+    pub sectors: u8,
+    ///last-modified unix timestamp
+    pub timestamp: u32,
+}
+
+impl ChunkSlot {
+    pub fn is_populated(&self) -> bool {
+        is_populated((self.offset, self.sectors))
+    }
+}
+
+///the two 4 KiB tables at the start of a region file, the cheap metadata pass of an indexer
+///
+///[`RegionHeader::from_bytes`] only needs the first [`HEADER_SIZE`] bytes of the file and
+///[`RegionHeader::to_bytes`] writes them back in the same layout
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct RegionHeader {
+    ///indexed like the header, `x + z * 32`
+    pub slots: Vec<ChunkSlot>,
+}
+
+impl RegionHeader {
+    ///parses the header at the start of `bytes`, anything after it is ignored
+    pub fn from_bytes(bytes: &[u8]) -> BinResult<Self> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(BinError::RegionHeaderTooShort {
+                len: bytes.len(),
+                needed: HEADER_SIZE,
+            });
+        }
+        let mut locations = FileReaderBE::new(bytes, 0);
+        let mut timestamps = FileReaderBE::new(bytes, SECTOR_SIZE);
+        let mut slots = Vec::with_capacity(CHUNKS_PER_REGION);
+        for _ in 0..CHUNKS_PER_REGION {
+            slots.push(ChunkSlot {
+                offset: locations.read_u24_be()?,
+                sectors: locations.read()?,
+                timestamp: timestamps.read()?,
+            });
+        }
+        Ok(Self { slots })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![0x00; HEADER_SIZE];
+        for (i, slot) in self.slots.iter().take(CHUNKS_PER_REGION).enumerate() {
+            let location = (slot.offset << 8) | slot.sectors as u32;
+            bytes[i * 4..i * 4 + 4].copy_from_slice(&location.to_be_bytes());
+            let pos = SECTOR_SIZE + i * 4;
+            bytes[pos..pos + 4].copy_from_slice(&slot.timestamp.to_be_bytes());
+        }
+        bytes
+    }
+
+    ///the slot of the chunk, `None` if the coordinates are outside the region
+    pub fn slot(&self, x: u8, z: u8) -> Option<&ChunkSlot> {
+        self.slots.get(chunk_index(x, z)?)
+    }
+
+    ///coordinates and slots of the populated chunks, in header order
+    pub fn populated(&self) -> impl Iterator<Item = (u8, u8, &ChunkSlot)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.is_populated())
+            .map(|(i, slot)| ((i % 32) as u8, (i / 32) as u8, slot))
+    }
+}
+
+///a slot holds a chunk only if both its sector offset and its sector count are nonzero,
+///like vanilla, which treats a zero count as a missing chunk
+fn is_populated((offset, sectors): (u32, u8)) -> bool {
+    offset != 0 && sectors != 0
+}
+
+///index of a chunk inside the header tables, `None` if the coordinates are outside the region
+fn chunk_index(x: u8, z: u8) -> Option<usize> {
+    if x >= 32 || z >= 32 {
+        return None;
+    }
+    Some(x as usize + z as usize * 32)
+}
+
+///parses the region coordinates from a `r.X.Z.mca` file name
+pub fn region_coordinates<P: AsRef<Path>>(path: P) -> Option<(i32, i32)> {
+    let name = path.as_ref().file_name()?.to_str()?;
+    let mut parts = name.split('.');
+    if parts.next()? != "r" {
+        return None;
+    }
+    let x = parts.next()?.parse().ok()?;
+    let z = parts.next()?.parse().ok()?;
+    if parts.next()? != "mca" || parts.next().is_some() {
+        return None;
+    }
+    Some((x, z))
+}
+
+///reads every chunk of every `r.X.Z.mca` file of a `region` directory
+///
+///yields [`WorldChunk`]s with the chunks' world coordinates, regions are opened as the iterator
+///reaches them and in no particular order, a chunk that fails to read yields its error noting
+///the chunk, a region that can't be opened yields a single error naming the file, at the
+///coordinates of the region's first chunk
+pub fn read_world_region_dir<P: AsRef<Path>>(
+    dir: P,
+) -> BinResult<impl Iterator<Item = WorldChunk>> {
+    let entries = std::fs::read_dir(dir)?;
+    Ok(entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let (region_x, region_z) = region_coordinates(&path)?;
+            Some((region_x, region_z, path))
+        })
+        .flat_map(|(region_x, region_z, path)| {
+            let chunks: Box<dyn Iterator<Item = _>> = match RegionFile::open(&path) {
+                Ok(region) => Box::new(chunks_of(region)),
+                Err(e) => Box::new(std::iter::once((
+                    0,
+                    0,
+                    Err(e.context(format!("Opening {}", path.display()))),
+                ))),
+            };
+            chunks.map(move |(x, z, chunk)| {
+                let chunk_x = region_x * 32 + x as i32;
+                let chunk_z = region_z * 32 + z as i32;
+                (region_x, region_z, chunk_x, chunk_z, chunk)
+            })
+        }))
+}
+
+///the populated chunks of a region owned or borrowed, with their coordinates within it,
+///what [`RegionFile::iter_chunks`] and [`read_world_region_dir`] are built on
+fn chunks_of<R: Borrow<RegionFile>>(region: R) -> impl Iterator<Item = (u8, u8, BinResult<Tag>)> {
+    (0..CHUNKS_PER_REGION)
+        .map(|i| ((i % 32) as u8, (i / 32) as u8))
+        .filter_map(move |(x, z)| {
+            let region = region.borrow();
+            if !region.location(x, z).is_some_and(is_populated) {
+                return None;
+            }
+            match region.read_chunk(x, z) {
+                Ok(chunk) => Some((x, z, Ok(chunk?))),
+                Err(e) => Some((x, z, Err(e.context(format!("Chunk ({}, {})", x, z))))),
+            }
+        })
+}
+
+fn out_of_region(x: u8, z: u8) -> BinError {
+    BinError::ChunkOutOfRegion { x, z }
+}
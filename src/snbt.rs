@@ -0,0 +1,158 @@
+use crate::nbt::{Compound, Tag, TagRef};
+use std::fmt::Write;
+
+///controls how [`Tag::to_snbt`] writes floats and doubles
+///
+///the default matches vanilla's output: `1.5f`, `1.0d`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SnbtConfig {
+    ///write `F`/`D` instead of `f`/`d`
+    pub uppercase_suffixes: bool,
+    ///suffix doubles with `d`, unsuffixed doubles always get a decimal point (`2.0`, `1.0e16`)
+    ///so they aren't read back as ints
+    pub double_suffix: bool,
+    ///keep the `.0` of integral values (`1.0f`) instead of dropping it (`1f`),
+    ///ignored for unsuffixed doubles
+    pub trailing_zero: bool,
+    ///write floats with the shortest representation that round-trips to the same float,
+    ///otherwise with the shortest round-trip representation of their widening to a double
+    ///(`0.1f` becomes `0.10000000149011612f`)
+    pub shortest: bool,
+}
+
+impl Default for SnbtConfig {
+    fn default() -> Self {
+        Self {
+            uppercase_suffixes: false,
+            double_suffix: true,
+            trailing_zero: true,
+            shortest: true,
+        }
+    }
+}
+
+impl Tag {
+    ///serializes the tag to SNBT, compound keys are sorted to keep the output stable
+    ///
+    ///NaN and infinities have no SNBT representation and are written as `NaN`/`inf`
+    pub fn to_snbt(&self, config: &SnbtConfig) -> String {
+        let mut out = String::new();
+        write_snbt(self.as_ref(), config, &mut out);
+        out
+    }
+}
+
+fn write_snbt(tag: TagRef, config: &SnbtConfig, out: &mut String) {
+    match tag {
+        TagRef::Byte(v) => write!(out, "{}b", v).unwrap(),
+        TagRef::Short(v) => write!(out, "{}s", v).unwrap(),
+        TagRef::Int(v) => write!(out, "{}", v).unwrap(),
+        TagRef::Long(v) => write!(out, "{}L", v).unwrap(),
+        TagRef::Float(v) => {
+            let v = if config.shortest {
+                format!("{:?}", v)
+            } else {
+                format!("{:?}", v as f64)
+            };
+            write_float(&v, Some('f'), config, out);
+        }
+        TagRef::Double(v) => {
+            let suffix = config.double_suffix.then_some('d');
+            write_float(&format!("{:?}", v), suffix, config, out);
+        }
+        TagRef::ByteArray(v) => write_array("B", v.iter().map(|v| format!("{}B", v)), out),
+        TagRef::String(v) => write_string(v, out),
+        TagRef::List(list) => {
+            out.push('[');
+            for (i, element) in list.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_snbt(element, config, out);
+            }
+            out.push(']');
+        }
+        TagRef::Compound(map) => {
+            let keys = sorted_keys(map);
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_key(key, out);
+                out.push(':');
+                write_snbt(map[key].as_ref(), config, out);
+            }
+            out.push('}');
+        }
+        TagRef::IntArray(v) => write_array("I", v.iter().map(|v| v.to_string()), out),
+        TagRef::LongArray(v) => write_array("L", v.iter().map(|v| format!("{}L", v)), out),
+    }
+}
+
+fn write_float(v: &str, suffix: Option<char>, config: &SnbtConfig, out: &mut String) {
+    let Some(suffix) = suffix else {
+        //only the decimal point tells SNBT an unsuffixed number isn't an int
+        let (mantissa, exponent) = v.split_at(v.find('e').unwrap_or(v.len()));
+        out.push_str(mantissa);
+        if mantissa.ends_with(|c: char| c.is_ascii_digit()) && !mantissa.contains('.') {
+            out.push_str(".0");
+        }
+        out.push_str(exponent);
+        return;
+    };
+    match v.strip_suffix(".0") {
+        Some(integral) if !config.trailing_zero => out.push_str(integral),
+        _ => out.push_str(v),
+    }
+    if config.uppercase_suffixes {
+        out.push(suffix.to_ascii_uppercase());
+    } else {
+        out.push(suffix);
+    }
+}
+
+fn write_array(prefix: &str, values: impl Iterator<Item = String>, out: &mut String) {
+    out.push('[');
+    out.push_str(prefix);
+    out.push(';');
+    out.push_str(&values.collect::<Vec<_>>().join(","));
+    out.push(']');
+}
+
+///the keys of `map` in the order [`Tag::to_snbt`] writes them
+pub(crate) fn sorted_keys(map: &Compound) -> Vec<&String> {
+    let mut keys: Vec<_> = map.keys().collect();
+    keys.sort();
+    keys
+}
+
+///keys made of `[A-Za-z0-9._+-]` are written bare, others are quoted
+fn write_key(key: &str, out: &mut String) {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '+' | '-'))
+    {
+        out.push_str(key);
+    } else {
+        write_string(key, out);
+    }
+}
+
+///quotes with `"`, or with `'` if the string contains a `"` but no `'`
+fn write_string(v: &str, out: &mut String) {
+    let quote = if v.contains('"') && !v.contains('\'') {
+        '\''
+    } else {
+        '"'
+    };
+    out.push(quote);
+    for c in v.chars() {
+        if c == quote || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push(quote);
+}
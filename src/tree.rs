@@ -0,0 +1,107 @@
+use crate::nbt::{tag_name, Tag, TagRef};
+use crate::snbt::sorted_keys;
+use std::fmt::{Display, Write};
+
+impl Tag {
+    ///renders the tag as an indented tree for people browsing NBT in a terminal
+    ///
+    ///every line is a key (or list index), the tag's type and, for everything but lists and
+    ///compounds, its value, arrays show at most `max_array_preview` values followed by
+    ///how many more there are, compound keys are sorted like [`Tag::to_snbt`] does
+    ///
+    ///```text
+    ///(Compound)
+    ///├─ Level (Compound)
+    ///│  ├─ Heights: IntArray[256] = [0, 0, … 254 more]
+    ///│  └─ xPos: Int = 3
+    ///└─ Tags (List of 1 String)
+    ///   └─ [0]: String = "a"
+    ///```
+    pub fn to_tree_string(&self, max_array_preview: usize) -> String {
+        let mut out = String::new();
+        write_node(None, self.as_ref(), "", max_array_preview, &mut out);
+        out.pop();
+        out
+    }
+}
+
+///writes the line of `tag` and, under it, its children prefixed with `indent`
+fn write_node(name: Option<&str>, tag: TagRef, indent: &str, preview: usize, out: &mut String) {
+    let name = name.unwrap_or("");
+    let separator = if name.is_empty() { "" } else { ": " };
+    match tag {
+        TagRef::Byte(v) => writeln!(out, "{}{}Byte = {}", name, separator, v).unwrap(),
+        TagRef::Short(v) => writeln!(out, "{}{}Short = {}", name, separator, v).unwrap(),
+        TagRef::Int(v) => writeln!(out, "{}{}Int = {}", name, separator, v).unwrap(),
+        TagRef::Long(v) => writeln!(out, "{}{}Long = {}", name, separator, v).unwrap(),
+        TagRef::Float(v) => writeln!(out, "{}{}Float = {:?}", name, separator, v).unwrap(),
+        TagRef::Double(v) => writeln!(out, "{}{}Double = {:?}", name, separator, v).unwrap(),
+        TagRef::String(v) => writeln!(out, "{}{}String = {:?}", name, separator, v).unwrap(),
+        TagRef::ByteArray(v) => write_array(name, separator, "ByteArray", v, preview, out),
+        TagRef::IntArray(v) => write_array(name, separator, "IntArray", v, preview, out),
+        TagRef::LongArray(v) => write_array(name, separator, "LongArray", v, preview, out),
+        TagRef::List(list) => {
+            let element = tag_name(list.element_id()).trim_start_matches("TAG_");
+            let element = element.replace('_', "");
+            let space = if name.is_empty() { "" } else { " " };
+            writeln!(out, "{}{}(List of {} {})", name, space, list.len(), element).unwrap();
+            let len = list.len();
+            for (i, element) in list.iter().enumerate() {
+                let index = format!("[{}]", i);
+                write_child(&index, element, i + 1 == len, indent, preview, out);
+            }
+        }
+        TagRef::Compound(map) => {
+            let space = if name.is_empty() { "" } else { " " };
+            writeln!(out, "{}{}(Compound)", name, space).unwrap();
+            let keys = sorted_keys(map);
+            let len = keys.len();
+            for (i, key) in keys.into_iter().enumerate() {
+                write_child(key, map[key].as_ref(), i + 1 == len, indent, preview, out);
+            }
+        }
+    }
+}
+
+fn write_child(
+    name: &str,
+    tag: TagRef,
+    last: bool,
+    indent: &str,
+    preview: usize,
+    out: &mut String,
+) {
+    let (branch, continuation) = if last {
+        ("└─ ", "   ")
+    } else {
+        ("├─ ", "│  ")
+    };
+    out.push_str(indent);
+    out.push_str(branch);
+    let indent = format!("{}{}", indent, continuation);
+    write_node(Some(name), tag, &indent, preview, out);
+}
+
+fn write_array<T: Display>(
+    name: &str,
+    separator: &str,
+    kind: &str,
+    values: &[T],
+    preview: usize,
+    out: &mut String,
+) {
+    write!(out, "{}{}{}[{}] = [", name, separator, kind, values.len()).unwrap();
+    for (i, v) in values.iter().take(preview).enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write!(out, "{}", v).unwrap();
+    }
+    if values.len() > preview {
+        if preview > 0 {
+            out.push_str(", ");
+        }
+        write!(out, "… {} more", values.len() - preview).unwrap();
+    }
+    out.push_str("]\n");
+}